@@ -0,0 +1,283 @@
+use crate::files;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{Datelike, Local, NaiveDate, TimeZone, Timelike};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::SystemTime;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+pub const EXTENSION: &str = "zip";
+
+/// Whether `path` names a save stored as a zip archive rather than a
+/// directory.
+pub fn is_archive(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == EXTENSION)
+}
+
+fn open(archive: &Path) -> Result<ZipArchive<File>> {
+    let file =
+        File::open(archive).with_context(|| format!("couldn't open archive {:?}", archive))?;
+    ZipArchive::new(file).with_context(|| format!("invalid archive {:?}", archive))
+}
+
+fn options() -> SimpleFileOptions {
+    SimpleFileOptions::default().compression_method(CompressionMethod::Deflated)
+}
+
+/// Reads one file out of an archive.
+pub fn read_file(archive: &Path, name: &str) -> Result<Vec<u8>> {
+    let mut zip = open(archive)?;
+    let mut entry = zip
+        .by_name(name)
+        .with_context(|| format!("no {} in archive {:?}", name, archive))?;
+    let mut data = vec![];
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// The folder every file in an archive is in, if they're all in the same
+/// one, as when a save's directory was zipped rather than its files.
+pub(crate) fn top_folder(archive: &Path) -> Result<Option<String>> {
+    let zip = open(archive)?;
+    let mut folder = None;
+    for name in zip.file_names() {
+        let first = name.split('/').next().unwrap_or_default();
+        if files::is_clutter(first) {
+            continue;
+        }
+        if !name.contains('/') {
+            return Ok(None);
+        }
+        match folder {
+            Some(f) if f != first => return Ok(None),
+            _ => folder = Some(first),
+        }
+    }
+    Ok(folder.map(str::to_string))
+}
+
+/// A file inside an archive.
+pub(crate) struct Entry {
+    pub name: String,
+    pub data: Vec<u8>,
+    modified: Option<DateTime>,
+}
+
+/// Converts a file time to a zip timestamp, which is in local time.
+fn zip_time(time: SystemTime) -> Option<DateTime> {
+    let local = chrono::DateTime::<Local>::from(time);
+    DateTime::from_date_and_time(
+        local.year().try_into().ok()?,
+        local.month() as u8,
+        local.day() as u8,
+        local.hour() as u8,
+        local.minute() as u8,
+        local.second() as u8,
+    )
+    .ok()
+}
+
+fn system_time(time: DateTime) -> Option<SystemTime> {
+    let naive =
+        NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())?
+            .and_hms_opt(
+                time.hour().into(),
+                time.minute().into(),
+                time.second().into(),
+            )?;
+    Some(Local.from_local_datetime(&naive).earliest()?.into())
+}
+
+/// Every file in an archive, with its contents.
+pub(crate) fn read_all(archive: &Path) -> Result<Vec<Entry>> {
+    let mut zip = open(archive)?;
+    let mut entries = vec![];
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let mut data = vec![];
+        entry
+            .read_to_end(&mut data)
+            .with_context(|| format!("couldn't read {} in archive {:?}", entry.name(), archive))?;
+        entries.push(Entry {
+            name: entry.name().to_string(),
+            data,
+            modified: entry.last_modified(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Writes `entries` as the archive at `archive`. They're written next to
+/// it under a hidden name first, so a failed write leaves any existing
+/// archive untouched.
+fn write_all(archive: &Path, entries: &[Entry]) -> Result<()> {
+    let name = archive
+        .file_name()
+        .with_context(|| format!("invalid archive path {:?}", archive))?;
+    let tmp = archive.with_file_name(format!(".nine_saves_write_{}", name.to_string_lossy()));
+    let result = write_entries(&tmp, entries).and_then(|_| {
+        fs::rename(&tmp, archive).with_context(|| format!("couldn't replace archive {:?}", archive))
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+fn write_entries(archive: &Path, entries: &[Entry]) -> Result<()> {
+    let file =
+        File::create(archive).with_context(|| format!("couldn't create archive {:?}", archive))?;
+    let mut zip = ZipWriter::new(file);
+    for entry in entries {
+        let options = match entry.modified {
+            Some(time) => options().last_modified_time(time),
+            None => options(),
+        };
+        zip.start_file(entry.name.as_str(), options)?;
+        zip.write_all(&entry.data)
+            .with_context(|| format!("couldn't write {} to archive {:?}", entry.name, archive))?;
+    }
+    zip.finish()
+        .with_context(|| format!("couldn't finish writing archive {:?}", archive))?;
+    Ok(())
+}
+
+/// Replaces or adds one file in an archive.
+pub fn write_file(archive: &Path, name: &str, data: &[u8]) -> Result<()> {
+    let mut entries = read_all(archive)?;
+    entries.retain(|e| e.name != name);
+    entries.push(Entry {
+        name: name.to_string(),
+        data: data.to_vec(),
+        modified: zip_time(SystemTime::now()),
+    });
+    write_all(archive, &entries)
+}
+
+fn collect(dir: &Path, prefix: &str, entries: &mut Vec<Entry>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("couldn't read directory {:?}", dir))? {
+        let entry = entry.with_context(|| format!("couldn't read directory {:?}", dir))?;
+        let path = entry.path();
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|n| anyhow!("non-unicode file name {:?}", n))?;
+        let name = format!("{}{}", prefix, name);
+        if entry
+            .file_type()
+            .with_context(|| format!("couldn't get type of {:?}", path))?
+            .is_dir()
+        {
+            collect(&path, &format!("{}/", name), entries)?;
+            continue;
+        }
+        entries.push(Entry {
+            data: fs::read(&path).with_context(|| format!("couldn't read {:?}", path))?,
+            modified: zip_time(files::modified(&path)?),
+            name,
+        });
+    }
+    Ok(())
+}
+
+/// Packs everything in `dir`, including subdirectories, into a new
+/// archive.
+pub fn create(dir: &Path, archive: &Path) -> Result<()> {
+    let mut entries = vec![];
+    collect(dir, "", &mut entries)?;
+    write_all(archive, &entries)
+}
+
+/// Unpacks an archive into `dir`, which is created if needed.
+pub fn extract(archive: &Path, dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("couldn't create directory {:?}", dir))?;
+    let mut zip = open(archive)?;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let relative = entry
+            .enclosed_name()
+            .with_context(|| format!("unsafe path {:?} in archive {:?}", entry.name(), archive))?;
+        let path = dir.join(relative);
+        if entry.is_dir() {
+            fs::create_dir_all(&path)
+                .with_context(|| format!("couldn't create directory {:?}", path))?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("couldn't create directory {:?}", parent))?;
+        }
+        let mut file =
+            File::create(&path).with_context(|| format!("couldn't create {:?}", path))?;
+        io::copy(&mut entry, &mut file).with_context(|| {
+            format!(
+                "couldn't extract {} from archive {:?}",
+                entry.name(),
+                archive
+            )
+        })?;
+        drop(file);
+        if let Some(time) = entry.last_modified().and_then(system_time) {
+            files::set_modified(&path, time)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn round_trip() {
+        let root = TempDir::new("archive");
+        let dir = root.join("save");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("meta.txt"), b"meta").unwrap();
+        fs::write(dir.join("flags.txt"), b"flags").unwrap();
+        fs::create_dir_all(dir.join("mods")).unwrap();
+        fs::write(dir.join("mods").join("data.txt"), b"nested").unwrap();
+        let old = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        files::set_modified(&dir.join("flags.txt"), old).unwrap();
+
+        let archive = root.join("save.zip");
+        create(&dir, &archive).unwrap();
+        assert_eq!(read_file(&archive, "meta.txt").unwrap(), b"meta");
+        write_file(&archive, "meta.txt", b"new").unwrap();
+        assert_eq!(read_file(&archive, "meta.txt").unwrap(), b"new");
+        assert_eq!(read_file(&archive, "flags.txt").unwrap(), b"flags");
+
+        let out = root.join("out");
+        extract(&archive, &out).unwrap();
+        assert_eq!(fs::read(out.join("flags.txt")).unwrap(), b"flags");
+        assert_eq!(
+            fs::read(out.join("mods").join("data.txt")).unwrap(),
+            b"nested"
+        );
+        // Zip timestamps only have two second precision.
+        let extracted = files::modified(&out.join("flags.txt")).unwrap();
+        assert!(extracted <= old && old.duration_since(extracted).unwrap().as_secs() <= 2);
+    }
+
+    #[test]
+    fn failed_writes_keep_the_archive() {
+        let root = TempDir::new("archive_failed");
+        let dir = root.join("save");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("meta.txt"), b"meta").unwrap();
+        let archive = root.join("save.zip");
+        create(&dir, &archive).unwrap();
+
+        // Something in the way of the temporary file makes the write fail.
+        fs::create_dir_all(root.join(".nine_saves_write_save.zip")).unwrap();
+        assert!(write_file(&archive, "meta.txt", b"new").is_err());
+        assert_eq!(read_file(&archive, "meta.txt").unwrap(), b"meta");
+    }
+}
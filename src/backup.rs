@@ -0,0 +1,384 @@
+use crate::save::{Save, SaveInfo, SavesData};
+use crate::sidecar::Sidecar;
+use crate::store::{FileHashes, Store};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest file inside each backup directory.
+const MANIFEST: &str = "backup.json";
+/// Name of the directory inside each backup that holds the save's files.
+const FILES: &str = "save";
+/// Name of the archive holding the save's files, for compressed backups.
+const ARCHIVE: &str = "save.zip";
+/// Name of the directory in the backups directory holding the files of
+/// deduplicated backups. Its leading dot keeps it from being read as a
+/// backup.
+const STORE: &str = ".store";
+
+/// How new backups store the save's files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupStorage {
+    /// A plain copy of the save's directory.
+    #[default]
+    Directory,
+    /// A zip archive.
+    Zip,
+    /// Files in a store shared between backups, so that files which haven't
+    /// changed since an earlier backup take no extra space.
+    Deduplicated,
+}
+
+/// What caused a backup to be made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// The save was about to be overwritten by another.
+    Overwrite,
+    /// The save was about to be deleted.
+    Delete,
+    /// The game wrote to the slot, and watch mode captured the result.
+    Snapshot,
+}
+
+impl Operation {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Overwrite => "overwritten",
+            Self::Delete => "deleted",
+            Self::Snapshot => "saved",
+        }
+    }
+}
+
+/// Information recorded alongside a backup's files.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BackupManifest {
+    /// Name of the save that was backed up, e.g. `Slot 2`.
+    pub source_name: String,
+    /// Where the save was when it was backed up.
+    pub source_path: PathBuf,
+    pub operation: Operation,
+    pub time: DateTime<Utc>,
+    /// The save's metadata at the time of the backup.
+    pub info: Option<SaveInfo>,
+    /// Why the save's metadata couldn't be read, if it couldn't.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The notes and tags of an external save.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sidecar: Option<Sidecar>,
+    /// For deduplicated backups, the hash of each file in the store.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files: Option<FileHashes>,
+}
+
+/// A backup in the backups directory.
+///
+/// Each backup is a directory named by its ID, containing a manifest and a
+/// copy of the save, either as a directory or a zip archive. Deduplicated
+/// backups hold only the manifest, which refers to files in the shared
+/// store. Backups made by older versions of Nine Saves are plain copies of
+/// the save with no manifest.
+#[derive(Clone, Debug)]
+pub struct Backup {
+    /// Unique ID, which is also the backup's directory name.
+    pub id: String,
+    pub path: PathBuf,
+    pub manifest: Option<BackupManifest>,
+    /// Why the manifest couldn't be read. Such a backup is still listed,
+    /// but can't be restored, and is never pruned.
+    pub error: Option<String>,
+    /// The backed up files.
+    pub save: Save,
+}
+
+fn write_manifest(path: &Path, manifest: &BackupManifest) -> Result<()> {
+    let data = sonic_rs::to_string_pretty(manifest).context("couldn't serialize manifest")?;
+    fs::write(path, data).with_context(|| format!("couldn't write backup manifest {:?}", path))
+}
+
+fn read_manifest(path: &Path) -> Result<BackupManifest> {
+    let data =
+        fs::read(path).with_context(|| format!("couldn't read backup manifest {:?}", path))?;
+    sonic_rs::from_slice(&data).with_context(|| format!("invalid backup manifest {:?}", path))
+}
+
+impl Backup {
+    /// Reads the backup stored at `path`. A manifest that can't be read is
+    /// reported in [`Backup::error`].
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.is_dir() {
+            return Err(anyhow!("there's no backup at {:?}", path));
+        }
+        let id = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .with_context(|| format!("invalid backup directory name {:?}", path))?
+            .to_string();
+        let manifest_path = path.join(MANIFEST);
+        let (manifest, error, files) = match manifest_path.exists() {
+            true => {
+                let (manifest, error) = match read_manifest(&manifest_path) {
+                    Ok(manifest) => (Some(manifest), None),
+                    Err(e) => (None, Some(format!("{:#}", e))),
+                };
+                let files = match path.join(ARCHIVE).exists() {
+                    true => path.join(ARCHIVE),
+                    false => path.join(FILES),
+                };
+                (manifest, error, files)
+            }
+            false => (None, None, path.to_owned()),
+        };
+        let save = Save {
+            name: match &manifest {
+                Some(m) => m.source_name.clone(),
+                None => id.clone(),
+            },
+            path: files,
+            nrp_backup: false,
+            exists: true,
+            info: manifest.as_ref().and_then(|m| m.info.clone()),
+            error: error
+                .clone()
+                .or_else(|| manifest.as_ref().and_then(|m| m.error.clone())),
+            sidecar: manifest
+                .as_ref()
+                .and_then(|m| m.sidecar.clone())
+                .unwrap_or_default(),
+        };
+        // The manifest's snapshot is authoritative; only older backups need
+        // decrypting.
+        let save = match (&manifest, &error) {
+            (None, None) => save.with_info_or_error(),
+            _ => save,
+        };
+        Ok(Backup {
+            id,
+            path: path.to_owned(),
+            manifest,
+            error,
+            save,
+        })
+    }
+
+    /// When the backup was made, if known.
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        self.manifest.as_ref().map(|m| m.time)
+    }
+
+    /// A one-line description such as
+    /// `Slot 2, overwritten 2024-06-20 18:03`.
+    pub fn describe(&self) -> String {
+        match &self.manifest {
+            Some(m) => format!(
+                "{}, {} {}",
+                m.source_name,
+                m.operation.describe(),
+                m.time.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            ),
+            None => self.id.clone(),
+        }
+    }
+}
+
+/// Loads every backup in `dir`, newest first. Backups whose manifest
+/// can't be read are included, with the reason in [`Backup::error`].
+pub fn backups_from_dir(dir: &Path) -> Result<Vec<Backup>> {
+    let mut backups: Vec<Backup> = fs::read_dir(dir)
+        .context("couldn't read backups directory")?
+        .filter_map(|x| x.ok())
+        .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        // Only directories with names that aren't text fail to load.
+        .filter_map(|p| Backup::load(&p).ok())
+        .collect();
+    backups.sort_by(|a, b| b.time().cmp(&a.time()).then_with(|| b.id.cmp(&a.id)));
+    Ok(backups)
+}
+
+/// Keeps only characters that are safe in a directory name on every OS.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+impl SavesData {
+    /// The store holding the files of deduplicated backups.
+    pub fn store(&self) -> Store {
+        Store::new(self.backups_dir.join(STORE))
+    }
+
+    /// Calls `f` with the backup's files, checking a deduplicated backup
+    /// out of the store for the duration.
+    pub(crate) fn with_backup_files<T>(
+        &self,
+        backup: &Backup,
+        f: impl FnOnce(&Save) -> Result<T>,
+    ) -> Result<T> {
+        let Some(files) = backup.manifest.as_ref().and_then(|m| m.files.as_ref()) else {
+            return f(&backup.save);
+        };
+        let result = self
+            .store()
+            .checkout(files, &backup.save.path)
+            .with_context(|| format!("couldn't check out backup {}", backup.id))
+            .and_then(|_| f(&backup.save));
+        let _ = fs::remove_dir_all(&backup.save.path);
+        result
+    }
+
+    /// Removes files from the store that no backup refers to any more, and
+    /// returns their paths. With `dry_run`, nothing is removed.
+    pub fn collect_garbage(&self, dry_run: bool) -> Result<Vec<PathBuf>> {
+        let backups = backups_from_dir(&self.backups_dir).context("failed to load backups")?;
+        self.store().collect_garbage(&backups, dry_run)
+    }
+
+    /// Makes the backups of the save that was at `from` belong to `to`,
+    /// after it's been renamed, so restoring them writes to where it is now.
+    pub(crate) fn redirect_backups(&self, from: &Path, to: &Save) -> Result<()> {
+        for backup in backups_from_dir(&self.backups_dir).context("failed to load backups")? {
+            let Some(mut manifest) = backup.manifest else {
+                continue;
+            };
+            if manifest.source_path == from {
+                manifest.source_name.clone_from(&to.name);
+                manifest.source_path.clone_from(&to.path);
+                write_manifest(&backup.path.join(MANIFEST), &manifest)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies `save` into a new backup, recording why.
+    ///
+    /// The backup's ID is its creation time followed by the save's name.
+    /// Creating its directory reserves the ID, so two backups can never
+    /// share a directory even if they're made in the same instant.
+    pub fn backup(&self, save: &Save, operation: Operation) -> Result<Backup> {
+        let time = Utc::now();
+        let base = format!(
+            "{}_{}",
+            time.format("%Y-%m-%d_%H-%M-%S-%3f"),
+            sanitize(&save.name)
+        );
+        fs::create_dir_all(&self.backups_dir).context("couldn't create backups directory")?;
+        let mut id = base.clone();
+        let mut n = 1;
+        let path = loop {
+            let path = self.backups_dir.join(&id);
+            match fs::create_dir(&path) {
+                Ok(()) => break path,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    n += 1;
+                    id = format!("{}-{}", base, n);
+                }
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("couldn't create backup directory {:?}", path))
+                }
+            }
+        };
+
+        let mut manifest = BackupManifest {
+            source_name: save.name.clone(),
+            source_path: save.path.clone(),
+            operation,
+            time,
+            info: save.info.clone(),
+            error: save.error.clone(),
+            sidecar: (!save.sidecar.notes.is_empty() || !save.sidecar.tags.is_empty()).then(|| {
+                Sidecar {
+                    checksums: None,
+                    ..save.sidecar.clone()
+                }
+            }),
+            files: None,
+        };
+        let files = match self.backup_storage {
+            BackupStorage::Directory | BackupStorage::Deduplicated => path.join(FILES),
+            BackupStorage::Zip => path.join(ARCHIVE),
+        };
+        let result = save
+            .copy(&files)
+            .with_context(|| format!("failed to back up save {}", save.name))
+            .and_then(|_| {
+                if self.backup_storage == BackupStorage::Deduplicated {
+                    // The copy only served to unpack the save, whatever form
+                    // it was in.
+                    manifest.files = Some(self.store().add_dir(&files)?);
+                    fs::remove_dir_all(&files).context("couldn't remove staged files")?;
+                }
+                write_manifest(&path.join(MANIFEST), &manifest)
+            });
+        if let Err(e) = result {
+            // Don't leave a partial backup behind to be mistaken for a
+            // complete one.
+            let _ = fs::remove_dir_all(&path);
+            return Err(e);
+        }
+
+        Backup::load(&path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{save, saves_data, write_save, TempDir};
+
+    #[test]
+    fn unique_ids() {
+        let root = TempDir::new("backup");
+        write_save(&root.join("slots").join("saveslot0"));
+        let save = save(root.join("slots").join("saveslot0"))
+            .with_decrypted_info()
+            .unwrap();
+        let data = saves_data(&root);
+
+        let a = data.backup(&save, Operation::Overwrite).unwrap();
+        let b = data.backup(&save, Operation::Delete).unwrap();
+        assert_ne!(a.id, b.id);
+        let backups = backups_from_dir(&data.backups_dir).unwrap();
+        assert_eq!(backups.len(), 2);
+        assert_eq!(backups[0].id, b.id);
+        assert_eq!(
+            backups[0].manifest.as_ref().unwrap().operation,
+            Operation::Delete
+        );
+        assert_eq!(backups[1].save.info.as_ref().unwrap().level(), 1);
+    }
+
+    #[test]
+    fn unreadable_manifests_are_listed() {
+        let root = TempDir::new("backup_unreadable");
+        write_save(&root.join("slots").join("saveslot0"));
+        let save = save(root.join("slots").join("saveslot0"))
+            .with_decrypted_info()
+            .unwrap();
+        let mut data = saves_data(&root);
+        data.backup(&save, Operation::Overwrite).unwrap();
+        let broken = data.backup(&save, Operation::Delete).unwrap();
+        fs::write(broken.path.join(MANIFEST), b"not json").unwrap();
+
+        data.refresh().unwrap();
+        assert_eq!(data.backups.len(), 2);
+        let broken = data.backups.iter().find(|b| b.id == broken.id).unwrap();
+        assert!(broken.error.is_some());
+        assert_eq!(broken.save.error, broken.error);
+        assert!(data.restore(broken).is_err());
+        data.prune_backups(false).unwrap();
+    }
+}
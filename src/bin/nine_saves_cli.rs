@@ -0,0 +1,445 @@
+use nine_saves::sidecar::parse_tags;
+use nine_saves::{
+    discovery, Config, Integrity, Save, SaveFlags, SaveQuery, SavesData, SlotWatcher, SortOrder,
+};
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// Headless interface to Nine Saves.
+///
+/// Uses the same directories as the GUI, and backs up anything it is about
+/// to overwrite or delete.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// The game's save directory, overriding the config file
+    #[arg(long, global = true)]
+    game_dir: Option<PathBuf>,
+    /// Directory for external saves, overriding the config file
+    #[arg(long, global = true)]
+    saves_dir: Option<PathBuf>,
+    /// Directory for backups, overriding the config file
+    #[arg(long, global = true)]
+    backups_dir: Option<PathBuf>,
+}
+
+impl Cli {
+    fn apply_to(&self, config: &mut Config) {
+        if self.game_dir.is_some() {
+            config.game_slots_dir.clone_from(&self.game_dir);
+        }
+        if self.saves_dir.is_some() {
+            config.external_saves_dir.clone_from(&self.saves_dir);
+        }
+        if self.backups_dir.is_some() {
+            config.backups_dir.clone_from(&self.backups_dir);
+        }
+    }
+}
+
+/// Orders for listing external saves, see [`SortOrder`].
+#[derive(Clone, Copy, ValueEnum)]
+enum Sort {
+    Name,
+    Level,
+    Playtime,
+    Gold,
+    Location,
+    Modified,
+}
+
+impl From<Sort> for SortOrder {
+    fn from(sort: Sort) -> Self {
+        match sort {
+            Sort::Name => SortOrder::Name,
+            Sort::Level => SortOrder::Level,
+            Sort::Playtime => SortOrder::Playtime,
+            Sort::Gold => SortOrder::Gold,
+            Sort::Location => SortOrder::Location,
+            Sort::Modified => SortOrder::Modified,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Saves(SavesCommand),
+    /// Show the config file location and the directories in use
+    Config {
+        /// Store the directories given on the command line in the config file
+        #[arg(long)]
+        write: bool,
+    },
+}
+
+/// Commands that work with saves, so they need the save directories.
+#[derive(Subcommand)]
+enum SavesCommand {
+    /// List the game's slots and the external saves
+    List {
+        /// Only list external saves whose name, location, notes or tags
+        /// contain this
+        #[arg(long)]
+        search: Option<String>,
+        /// Only list external saves with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Only list external saves in this game mode
+        #[arg(long)]
+        mode: Option<u8>,
+        /// How to order external saves
+        #[arg(long, value_enum, default_value = "name")]
+        sort: Sort,
+    },
+    /// List backups
+    Backups,
+    /// Copy a game slot (1-4) to an external save
+    SaveSlot {
+        slot: usize,
+        name: String,
+        /// Replace the external save if it already exists
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Write an external save into a game slot (1-4)
+    Load { name: String, slot: usize },
+    /// Restore a backup to where it came from, backing up what it replaces
+    Restore { id: String },
+    /// Delete an external save
+    Delete { name: String },
+    /// Show or change the notes and tags of an external save
+    Notes {
+        name: String,
+        /// Replace the notes
+        #[arg(long)]
+        set: Option<String>,
+        /// Replace the tags, separated by commas
+        #[arg(long)]
+        tags: Option<String>,
+    },
+    /// Rename an external save
+    Rename { name: String, new_name: String },
+    /// Copy an external save to a new one
+    Duplicate { name: String, new_name: String },
+    /// Add a save directory or zip archive from anywhere, such as one shared
+    /// by a friend, as an external save
+    Import { path: PathBuf, name: String },
+    /// Move an external save into a folder, e.g. `bosses`. An empty folder
+    /// moves it out of any folder.
+    Move { name: String, folder: String },
+    /// Delete a game slot (1-4)
+    DeleteSlot { slot: usize },
+    /// Reverse the last load, overwrite or delete, even one made in an
+    /// earlier session
+    Undo,
+    /// Remove backups that the retention policy in the config file doesn't
+    /// keep. This also happens after every other command that makes backups.
+    Prune {
+        /// Only show what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check every external save against the checksums recorded when it
+    /// was written, and record them for saves that have none
+    Verify,
+    /// Remove files from the deduplicated backup store that no backup
+    /// refers to any more
+    Gc {
+        /// Only show what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Keep running, and snapshot each game slot whenever the game saves
+    /// to it. Old snapshots are removed according to the retention policy.
+    Watch {
+        /// Seconds between checks for changes
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+    /// Show the progression flags of an external save
+    Flags {
+        name: String,
+        /// Treat NAME as a game slot number (1-4)
+        #[arg(long)]
+        slot: bool,
+        /// Only show flags whose key contains this
+        #[arg(long)]
+        filter: Option<String>,
+    },
+}
+
+fn describe(save: &Save) -> String {
+    let description = match &save.info {
+        Some(info) => format!(
+            "{}: level {}, {}, {}",
+            save.name,
+            info.level(),
+            info.formatted_time(),
+            info.location().unwrap_or("unknown location")
+        ),
+        None => match &save.error {
+            Some(error) => format!("{}: unreadable ({})", save.name, error),
+            None => format!("{}: empty", save.name),
+        },
+    };
+    match save.sidecar.tags.is_empty() {
+        true => description,
+        false => format!("{} [{}]", description, save.sidecar.tags.join(", ")),
+    }
+}
+
+fn slot(data: &SavesData, num: usize) -> Result<&Save> {
+    data.slot(num)
+        .ok_or_else(|| anyhow!("no slot {}, slots are numbered 1 to 4", num))
+}
+
+fn save<'a>(data: &'a SavesData, name: &str) -> Result<&'a Save> {
+    data.save(name)
+        .ok_or_else(|| anyhow!("no external save named {:?}", name))
+}
+
+/// Applies the retention policy after a command that made backups. The
+/// command itself already succeeded, so a failure is only reported.
+fn prune(data: &SavesData) {
+    if let Err(e) = data.prune_backups(false) {
+        eprintln!("warning: couldn't prune backups: {:?}", e);
+    }
+}
+
+/// Prints the config file location and the directories in use. This works
+/// even when the game's directory can't be found, since it's how the user
+/// finds out why.
+fn show_config(cli: &Cli, config: &Config, write: bool) -> Result<()> {
+    if write {
+        let mut file_config = Config::load_file()?;
+        cli.apply_to(&mut file_config);
+        file_config.save()?;
+    }
+    println!("Config file: {}", Config::path()?.display());
+    match SavesData::with_config(config) {
+        Ok(data) => {
+            println!("Game slots: {}", data.game_slots_dir.display());
+            println!("External saves: {}", data.external_saves_dir.display());
+            println!("Backups: {}", data.backups_dir.display());
+        }
+        Err(e) => println!("Couldn't work out the directories to use: {:#}", e),
+    }
+    let candidates = discovery::candidate_save_directories()?;
+    if !candidates.is_empty() {
+        println!("Detected game save directories, most recent first:");
+        for dir in candidates {
+            println!("  {}", dir.display());
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let mut config = Config::load()?;
+    cli.apply_to(&mut config);
+
+    let command = match cli.command {
+        Command::Config { write } => return show_config(&cli, &config, write),
+        Command::Saves(command) => command,
+    };
+    let mut data = SavesData::with_config(&config)?;
+    data.refresh()?;
+    if let Some(e) = &data.journal_error {
+        eprintln!(
+            "warning: nothing can be undone, the journal is unreadable: {}",
+            e
+        );
+    }
+
+    match command {
+        SavesCommand::List {
+            search,
+            tag,
+            mode,
+            sort,
+        } => {
+            println!("Game slots:");
+            for s in &data.slots {
+                println!("  {}", describe(s));
+            }
+            println!("External saves:");
+            let query = SaveQuery {
+                search: search.unwrap_or_default(),
+                tag,
+                game_mode: mode,
+                sort: sort.into(),
+            };
+            for i in query.apply(&data.saves) {
+                println!("  {}", describe(&data.saves[i]));
+            }
+        }
+        SavesCommand::Backups => {
+            for backup in &data.backups {
+                println!("{}", backup.id);
+                println!("  {}", backup.describe());
+                println!("  {}", describe(&backup.save));
+            }
+        }
+        SavesCommand::SaveSlot {
+            slot: num,
+            name,
+            overwrite,
+        } => {
+            let slot = slot(&data, num)?;
+            match data.save(&name) {
+                Some(existing) if overwrite => {
+                    data.write_slot_to_external(slot, existing)?;
+                    prune(&data);
+                }
+                Some(_) => {
+                    return Err(anyhow!(
+                        "external save {:?} already exists, pass --overwrite to replace it",
+                        name
+                    ))
+                }
+                None => {
+                    data.save_slot_to_new(slot, &name)?;
+                }
+            }
+        }
+        SavesCommand::Load { name, slot: num } => {
+            data.write_to_slot(save(&data, &name)?, slot(&data, num)?)?;
+            prune(&data);
+        }
+        SavesCommand::Restore { id } => {
+            let backup = data
+                .backups
+                .iter()
+                .find(|b| b.id == id)
+                .ok_or_else(|| anyhow!("no backup with ID {:?}", id))?;
+            data.restore(backup)?;
+            prune(&data);
+        }
+        SavesCommand::Delete { name } => {
+            data.delete_external(save(&data, &name)?)?;
+            prune(&data);
+        }
+        SavesCommand::Notes { name, set, tags } => {
+            let save = save(&data, &name)?;
+            if set.is_none() && tags.is_none() {
+                if !save.sidecar.tags.is_empty() {
+                    println!("Tags: {}", save.sidecar.tags.join(", "));
+                }
+                println!("{}", save.sidecar.notes);
+                return Ok(());
+            }
+            save.set_notes(
+                set.as_deref().unwrap_or(&save.sidecar.notes),
+                match tags {
+                    Some(tags) => parse_tags(&tags),
+                    None => save.sidecar.tags.clone(),
+                },
+            )?;
+        }
+        SavesCommand::Rename { name, new_name } => {
+            data.rename_external(save(&data, &name)?, &new_name)?;
+        }
+        SavesCommand::Duplicate { name, new_name } => {
+            data.duplicate_external(save(&data, &name)?, &new_name)?;
+        }
+        SavesCommand::Import { path, name } => {
+            data.import(&path, &name)?;
+        }
+        SavesCommand::Move { name, folder } => {
+            data.move_external(save(&data, &name)?, &folder)?;
+        }
+        SavesCommand::Undo => match data.undo()? {
+            Some(entry) => {
+                println!("Undid: {}", entry.description);
+                prune(&data);
+            }
+            None => println!("Nothing to undo."),
+        },
+        SavesCommand::DeleteSlot { slot: num } => {
+            let slot = slot(&data, num)?;
+            if !slot.exists {
+                return Err(anyhow!("{} is already empty", slot.name));
+            }
+            data.delete_slot(slot)?;
+            prune(&data);
+        }
+        SavesCommand::Prune { dry_run } => {
+            if data.retention.is_unlimited() {
+                println!("No retention policy is configured, so only old snapshots are removed.");
+            }
+            for backup in data.prune_backups(dry_run)? {
+                match dry_run {
+                    true => println!("would remove {}", backup.id),
+                    false => println!("removed {}", backup.id),
+                }
+            }
+        }
+        SavesCommand::Verify => {
+            let mut damaged = 0;
+            for (save, integrity) in data.verify_library()? {
+                match integrity {
+                    Integrity::Intact => println!("{}: ok", save.name),
+                    Integrity::Unrecorded => println!("{}: checksums recorded", save.name),
+                    Integrity::Damaged(files) => {
+                        damaged += 1;
+                        println!("{}: DAMAGED ({})", save.name, files.join(", "));
+                    }
+                }
+            }
+            if damaged > 0 {
+                return Err(anyhow!("{} damaged saves", damaged));
+            }
+        }
+        SavesCommand::Gc { dry_run } => {
+            let removed = data.collect_garbage(dry_run)?;
+            match dry_run {
+                true => println!("would remove {} unreferenced files", removed.len()),
+                false => println!("removed {} unreferenced files", removed.len()),
+            }
+        }
+        SavesCommand::Watch { interval } => {
+            let mut watcher = SlotWatcher::new(&data)?;
+            println!("Watching {}", data.game_slots_dir.display());
+            loop {
+                thread::sleep(Duration::from_secs(interval));
+                // A daemon shouldn't stop over one failed snapshot.
+                let result = watcher.poll(&data).and_then(|snapshots| {
+                    for snapshot in &snapshots {
+                        println!("{}", snapshot.id);
+                        println!("  {}", describe(&snapshot.save));
+                    }
+                    match snapshots.is_empty() {
+                        true => Ok(()),
+                        false => data.prune_backups(false).map(|_| ()),
+                    }
+                });
+                if let Err(e) = result {
+                    eprintln!("{:?}", e);
+                }
+            }
+        }
+        SavesCommand::Flags {
+            name,
+            slot: is_slot,
+            filter,
+        } => {
+            let save = match is_slot {
+                true => slot(&data, name.parse()?)?,
+                false => save(&data, &name)?,
+            };
+            let flags = SaveFlags::decrypt_from(save)?;
+            for (key, value) in flags.matching(filter.as_deref().unwrap_or("")) {
+                println!("{} = {}", key, value);
+            }
+        }
+    }
+    Ok(())
+}
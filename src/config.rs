@@ -0,0 +1,95 @@
+use crate::backup::BackupStorage;
+use crate::retention::RetentionPolicy;
+
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+pub const GAME_DIR_VAR: &str = "NINE_SAVES_GAME_DIR";
+pub const SAVES_DIR_VAR: &str = "NINE_SAVES_SAVES_DIR";
+pub const BACKUPS_DIR_VAR: &str = "NINE_SAVES_BACKUPS_DIR";
+
+/// User settings, read from `config.json` in the OS config directory
+/// (e.g. `~/.config/nine_saves/config.json` on Linux).
+///
+/// Any directory left unset falls back to the default location. The
+/// environment variables [`GAME_DIR_VAR`], [`SAVES_DIR_VAR`] and
+/// [`BACKUPS_DIR_VAR`] take precedence over the file.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// The game's own save directory, containing the `saveslotN` folders.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game_slots_dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_saves_dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backups_dir: Option<PathBuf>,
+    /// Which backups to keep when pruning.
+    #[serde(skip_serializing_if = "RetentionPolicy::is_unlimited")]
+    pub retention: RetentionPolicy,
+    pub backup_storage: BackupStorage,
+    /// Store new external saves as zip archives.
+    pub compress_saves: bool,
+    /// Start the GUI with automatic snapshots of the game's slots turned on.
+    pub watch_slots: bool,
+}
+
+impl Config {
+    /// Where the config file is stored.
+    pub fn path() -> Result<PathBuf> {
+        let base_dirs = BaseDirs::new().context("couldn't get base directories for OS")?;
+        Ok(base_dirs
+            .config_dir()
+            .join("nine_saves")
+            .join("config.json"))
+    }
+
+    /// Reads the config file, if there is one, and applies environment
+    /// variable overrides.
+    pub fn load() -> Result<Self> {
+        let mut config = Self::load_file()?;
+        config.apply_env();
+        Ok(config)
+    }
+
+    /// Reads only the config file, ignoring the environment.
+    pub fn load_file() -> Result<Self> {
+        let path = Self::path()?;
+        let config = match path.exists() {
+            true => {
+                let data = fs::read(&path)
+                    .with_context(|| format!("couldn't read config file {:?}", path))?;
+                sonic_rs::from_slice(&data)
+                    .with_context(|| format!("invalid config file {:?}", path))?
+            }
+            false => Config::default(),
+        };
+        Ok(config)
+    }
+
+    /// Writes the config file, creating its directory if needed.
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("couldn't create config directory")?;
+        }
+        let data = sonic_rs::to_string_pretty(self).context("couldn't serialize config")?;
+        fs::write(&path, data).with_context(|| format!("couldn't write config file {:?}", path))
+    }
+
+    fn apply_env(&mut self) {
+        for (var, field) in [
+            (GAME_DIR_VAR, &mut self.game_slots_dir),
+            (SAVES_DIR_VAR, &mut self.external_saves_dir),
+            (BACKUPS_DIR_VAR, &mut self.backups_dir),
+        ] {
+            if let Some(value) = env::var_os(var).filter(|v| !v.is_empty()) {
+                *field = Some(PathBuf::from(value));
+            }
+        }
+    }
+}
@@ -1,23 +1,29 @@
+use crate::flags::SaveFlags;
 use crate::save::{Save, SaveInfo};
 
 use anyhow::{Context, Result};
 use base64::prelude::*;
 use libaes::Cipher;
-use std::fs;
 
 const KEY: [u8; 16] = *b"1234567812345678";
 
 impl Save {
     fn encrypted_metadata(&self) -> Result<Vec<u8>> {
-        fs::read(self.path.join("meta.txt")).context("couldn't read metadata file")
+        self.read_file("meta.txt")
+            .context("couldn't read metadata file")
+    }
+    fn write_encrypted_metadata(&self, data: &[u8]) -> Result<()> {
+        self.write_file("meta.txt", data)
+            .context("couldn't write metadata file")
     }
-    #[allow(dead_code)]
     fn encrypted_flags(&self) -> Result<Vec<u8>> {
-        fs::read(self.path.join("flags.txt")).context("couldn't read flags file")
+        self.read_file("flags.txt")
+            .context("couldn't read flags file")
     }
 }
 
-fn decrypt(data: &[u8]) -> Result<Vec<u8>> {
+/// Decodes base64 `data` and decrypts it with the game's key.
+pub fn decrypt(data: &[u8]) -> Result<Vec<u8>> {
     let ciphertext = BASE64_STANDARD
         .decode(data)
         .context("error decoding base64")?;
@@ -26,23 +32,86 @@ fn decrypt(data: &[u8]) -> Result<Vec<u8>> {
     Ok(decrypted)
 }
 
+/// Encrypts `data` with the game's key and encodes it as base64. This is
+/// the inverse of [`decrypt`].
+pub fn encrypt(data: &[u8]) -> Vec<u8> {
+    let cipher = Cipher::new_128(&KEY);
+    let encrypted = cipher.cbc_encrypt(&KEY, data);
+    BASE64_STANDARD.encode(encrypted).into_bytes()
+}
+
 impl SaveInfo {
+    /// Reads and decrypts `meta.txt` from `save`.
     pub fn decrypt_from(save: &Save) -> Result<Self> {
         let data = save.encrypted_metadata()?;
         let decrypted = decrypt(&data)?;
-        let info: SaveInfo = sonic_rs::from_slice(&decrypted).context("invalid json")?;
+        SaveInfo::parse(&decrypted)
+    }
+    /// Encrypts this metadata and writes it to `save`'s `meta.txt`.
+    pub fn encrypt_to(&self, save: &Save) -> Result<()> {
+        save.write_encrypted_metadata(&encrypt(&self.to_json()?))
+    }
+}
 
-        Ok(info)
+impl SaveFlags {
+    /// Reads and decrypts `flags.txt` from `save`.
+    pub fn decrypt_from(save: &Save) -> Result<Self> {
+        let data = save.encrypted_flags()?;
+        let decrypted = decrypt(&data)?;
+        SaveFlags::parse(&decrypted)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::{save, TempDir};
+    use std::fs;
+
+    const DATA: &str = "1s5HvVtuLGW3T+UEb8oZh7+PEknj0CgMijOHVWILDy7hC9ah92LSPHX5OKzY4LOg9P3IpdGzGKMZfUWOc/sfEiYS2MsXRICTdDV/yfAZI6ovIMZjI6BiS7vwqcsL3lbEPkUbP9HBJC+agWNdh6F/c+9zUlrq6hDbGCyNpgMYc/Bmnz2aaOK3eYtqxW7YNQ9GiN23YtLAet0SEHYaUk/oE1HWXQIfZiXjN6DLJwkwWq6C4q6LfoZo6Ss99aF20qo9s5MkEXhaoRIXBWcxvM8GamkCTES8mBduzpmFDZtHQn27UVSmhLNbfh9oBs5WjeErhZABDRQqTzTtCvK8YI5DbUXupX2mFomp68sOHoxGegw45tR7hLZdl8lc7aP+XxZR8FpTRLnCKcw1uSozHP3qQZvC5tLTx7QH8A9CSh4wG8iwMs/06qIwqLCKJznn/+wDUY5X/yfUafV5pexXm/LRkUjKuV+igzc6ULT6zPLdRogobvqeKruAIxUPibG7mfjXYXs37Jw4Ld111lJxP16X5gFZixcTbqG6IMg/SqegyZ6RZ3yrpxv7g54WsMq7tzuJ";
 
     #[test]
     fn decryption() {
-        let data = "1s5HvVtuLGW3T+UEb8oZh7+PEknj0CgMijOHVWILDy7hC9ah92LSPHX5OKzY4LOg9P3IpdGzGKMZfUWOc/sfEiYS2MsXRICTdDV/yfAZI6ovIMZjI6BiS7vwqcsL3lbEPkUbP9HBJC+agWNdh6F/c+9zUlrq6hDbGCyNpgMYc/Bmnz2aaOK3eYtqxW7YNQ9GiN23YtLAet0SEHYaUk/oE1HWXQIfZiXjN6DLJwkwWq6C4q6LfoZo6Ss99aF20qo9s5MkEXhaoRIXBWcxvM8GamkCTES8mBduzpmFDZtHQn27UVSmhLNbfh9oBs5WjeErhZABDRQqTzTtCvK8YI5DbUXupX2mFomp68sOHoxGegw45tR7hLZdl8lc7aP+XxZR8FpTRLnCKcw1uSozHP3qQZvC5tLTx7QH8A9CSh4wG8iwMs/06qIwqLCKJznn/+wDUY5X/yfUafV5pexXm/LRkUjKuV+igzc6ULT6zPLdRogobvqeKruAIxUPibG7mfjXYXs37Jw4Ld111lJxP16X5gFZixcTbqG6IMg/SqegyZ6RZ3yrpxv7g54WsMq7tzuJ".as_bytes();
-        let decrypted = decrypt(&data).unwrap();
+        let data = DATA.as_bytes();
+        let decrypted = decrypt(data).unwrap();
+        assert!(SaveInfo::parse(&decrypted).is_ok());
+    }
+
+    #[test]
+    fn encryption_round_trip() {
+        let decrypted = decrypt(DATA.as_bytes()).unwrap();
+        assert_eq!(encrypt(&decrypted), DATA.as_bytes());
+    }
+
+    #[test]
+    fn rewrites_identical_metadata() {
+        let root = TempDir::new("decryption");
+        let save = save(root.join("saveslot0"));
+        fs::create_dir_all(&save.path).unwrap();
+        fs::write(save.path.join("meta.txt"), DATA).unwrap();
+
+        let info = SaveInfo::decrypt_from(&save).unwrap();
+        info.encrypt_to(&save).unwrap();
+        assert_eq!(
+            fs::read(save.path.join("meta.txt")).unwrap(),
+            DATA.as_bytes()
+        );
+    }
+
+    #[test]
+    fn metadata_round_trip() {
+        let decrypted = decrypt(DATA.as_bytes()).unwrap();
+        let mut info = SaveInfo::parse(&decrypted).unwrap();
+        assert!(info.get("lastPos").is_some());
+        assert_eq!(info.to_json().unwrap(), decrypted);
+
+        info.set("gold", 1000).unwrap();
+        assert_eq!(info.gold(), 1000);
+        let patched = String::from_utf8(decrypted)
+            .unwrap()
+            .replace("\"gold\":889", "\"gold\":1000");
+        assert_eq!(info.to_json().unwrap(), patched.as_bytes());
+        assert!(info.set("level", "high").is_err());
+        assert_eq!(info.level(), 2);
     }
 }
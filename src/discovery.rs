@@ -0,0 +1,289 @@
+use crate::config::GAME_DIR_VAR;
+
+use anyhow::{anyhow, Context, Result};
+use directories::BaseDirs;
+use regex::Regex;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const NS_ID: &str = "1809540";
+
+/// Steam installations to look in, including the Flatpak one.
+fn steam_roots(base_dirs: &BaseDirs) -> Vec<PathBuf> {
+    let home = base_dirs.home_dir();
+    vec![
+        base_dirs.data_dir().join("Steam"),
+        home.join(".steam").join("steam"),
+        home.join(".steam").join("root"),
+        home.join(".var")
+            .join("app")
+            .join("com.valvesoftware.Steam")
+            .join(".local")
+            .join("share")
+            .join("Steam"),
+    ]
+}
+
+/// Extracts the library paths from the contents of a
+/// `libraryfolders.vdf` file.
+pub fn parse_library_folders(vdf: &str) -> Vec<PathBuf> {
+    let re = Regex::new(r#""path"\s+"((?:[^"\\]|\\.)*)""#).expect("valid regex");
+    re.captures_iter(vdf)
+        .map(|caps| PathBuf::from(caps[1].replace("\\\\", "\\")))
+        .collect()
+}
+
+/// Every Steam library folder, found through each Steam installation's
+/// `libraryfolders.vdf`. The installations themselves are libraries too.
+fn steam_libraries(base_dirs: &BaseDirs) -> Vec<PathBuf> {
+    let mut libraries = vec![];
+    for root in steam_roots(base_dirs) {
+        for vdf in [
+            root.join("steamapps").join("libraryfolders.vdf"),
+            root.join("config").join("libraryfolders.vdf"),
+        ] {
+            if let Ok(contents) = fs::read_to_string(vdf) {
+                libraries.extend(parse_library_folders(&contents));
+            }
+        }
+        libraries.push(root);
+    }
+    libraries
+}
+
+fn proton_prefix(library: &Path) -> PathBuf {
+    let mut path = library.to_owned();
+    path.extend(&["steamapps", "compatdata", NS_ID, "pfx"]);
+    path
+}
+
+/// Finds Wine prefixes (directories containing `drive_c`) in `dir`,
+/// looking up to `depth` levels down.
+fn prefixes_in(dir: &Path, depth: usize, prefixes: &mut Vec<PathBuf>) {
+    if dir.join("drive_c").is_dir() {
+        prefixes.push(dir.to_owned());
+    } else if depth > 0 {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            if entry.path().is_dir() {
+                prefixes_in(&entry.path(), depth - 1, prefixes);
+            }
+        }
+    }
+}
+
+/// Wine prefixes outside of Steam: `WINEPREFIX`, the default `~/.wine`,
+/// and the places Lutris, Heroic and Bottles put theirs.
+fn wine_prefixes(base_dirs: &BaseDirs) -> Vec<PathBuf> {
+    let wine_prefix = env::var_os("WINEPREFIX").filter(|p| !p.is_empty());
+    wine_prefixes_in(
+        base_dirs.home_dir(),
+        base_dirs.data_dir(),
+        wine_prefix.as_deref().map(Path::new),
+    )
+}
+
+/// [`wine_prefixes`] for the home directory `home`, data directory `data`
+/// and `WINEPREFIX` `wine_prefix`.
+fn wine_prefixes_in(home: &Path, data: &Path, wine_prefix: Option<&Path>) -> Vec<PathBuf> {
+    let mut prefixes = vec![];
+    if let Some(prefix) = wine_prefix {
+        prefixes_in(prefix, 0, &mut prefixes);
+    }
+    prefixes_in(&home.join(".wine"), 0, &mut prefixes);
+    // Lutris installs to ~/Games/<game>, Heroic to
+    // ~/Games/Heroic/Prefixes/default/<game>.
+    let games = home.join("Games");
+    prefixes_in(&games, 1, &mut prefixes);
+    prefixes_in(&games.join("Heroic").join("Prefixes"), 2, &mut prefixes);
+    prefixes_in(&data.join("lutris").join("prefixes"), 1, &mut prefixes);
+    prefixes_in(&data.join("bottles").join("bottles"), 1, &mut prefixes);
+    prefixes_in(
+        &home
+            .join(".var")
+            .join("app")
+            .join("com.usebottles.bottles")
+            .join("data")
+            .join("bottles")
+            .join("bottles"),
+        1,
+        &mut prefixes,
+    );
+    prefixes
+}
+
+/// Every Wine prefix the game might be installed in, including Steam's
+/// Proton prefixes, whether or not it exists.
+fn possible_prefixes(base_dirs: &BaseDirs) -> Vec<PathBuf> {
+    let mut prefixes: Vec<PathBuf> = steam_libraries(base_dirs)
+        .iter()
+        .map(|library| proton_prefix(library))
+        .collect();
+    prefixes.extend(wine_prefixes(base_dirs));
+    prefixes
+}
+
+/// The game's save directory for each Windows user in a Wine prefix.
+fn prefix_save_directories(prefix: &Path) -> Vec<PathBuf> {
+    let Ok(users) = fs::read_dir(prefix.join("drive_c").join("users")) else {
+        return vec![];
+    };
+    users
+        .filter_map(|e| e.ok())
+        .map(|user| {
+            let mut path = user.path();
+            path.extend(&["AppData", "LocalLow", "RedCandleGames", "NineSols"]);
+            path
+        })
+        .collect()
+}
+
+/// Every place the game's saves could be on this OS, whether or not it
+/// exists.
+fn possible_save_directories(base_dirs: &BaseDirs) -> Vec<PathBuf> {
+    if cfg!(target_os = "windows") {
+        let mut path = base_dirs.home_dir().to_owned();
+        path.extend(&["AppData", "LocalLow", "RedCandleGames", "NineSols"]);
+        vec![path]
+    } else if cfg!(target_os = "macos") {
+        let mut path = base_dirs.data_dir().to_owned();
+        path.extend(&["RedCandleGames", "NineSols"]);
+        vec![path]
+    } else if cfg!(target_os = "linux") {
+        possible_prefixes(base_dirs)
+            .iter()
+            .flat_map(|prefix| prefix_save_directories(prefix))
+            .collect()
+    } else {
+        vec![]
+    }
+}
+
+/// What to tell the user was searched when nothing is found.
+fn searched_locations(base_dirs: &BaseDirs) -> Vec<PathBuf> {
+    match cfg!(target_os = "linux") {
+        true => possible_prefixes(base_dirs),
+        false => possible_save_directories(base_dirs),
+    }
+}
+
+/// The most recent modification time of a save directory or anything
+/// directly inside it.
+fn last_modified(dir: &Path) -> Option<SystemTime> {
+    let own = fs::metadata(dir).and_then(|m| m.modified()).ok();
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().and_then(|m| m.modified()).ok())
+        .chain(own)
+        .max()
+}
+
+/// Every existing Nine Sols save directory, most recently used first.
+pub fn candidate_save_directories() -> Result<Vec<PathBuf>> {
+    let base_dirs = BaseDirs::new().context("couldn't get base directories for OS")?;
+    Ok(existing_save_directories(&base_dirs))
+}
+
+fn existing_save_directories(base_dirs: &BaseDirs) -> Vec<PathBuf> {
+    let mut candidates: Vec<(PathBuf, Option<SystemTime>)> = vec![];
+    for path in possible_save_directories(base_dirs) {
+        // The same library is often reachable through several symlinks.
+        let Ok(canonical) = path.canonicalize() else {
+            continue;
+        };
+        if !candidates.iter().any(|(p, _)| *p == canonical) {
+            let modified = last_modified(&canonical);
+            candidates.push((canonical, modified));
+        }
+    }
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.1));
+    candidates.into_iter().map(|(p, _)| p).collect()
+}
+
+/// The most recently used Nine Sols save directory.
+pub fn save_directory() -> Result<PathBuf> {
+    let base_dirs = BaseDirs::new().context("couldn't get base directories for OS")?;
+    match existing_save_directories(&base_dirs).into_iter().next() {
+        Some(path) => Ok(path),
+        None => {
+            let searched: Vec<String> = searched_locations(&base_dirs)
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect();
+            Err(anyhow!("Could not find Nine Sols save directory. Searched:\n{}\nYou can set game_slots_dir in the config file or the {} environment variable to where your saves are stored. Please also report this bug along with that path.", searched.join("\n"), GAME_DIR_VAR))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn library_folders() {
+        let vdf = r#"
+"libraryfolders"
+{
+	"0"
+	{
+		"path"		"/home/user/.local/share/Steam"
+		"label"		""
+		"apps"
+		{
+			"228980"		"0"
+		}
+	}
+	"1"
+	{
+		"path"		"/run/media/mmcblk0p1"
+		"apps"
+		{
+			"1809540"		"10000"
+		}
+	}
+}
+"#;
+        assert_eq!(
+            parse_library_folders(vdf),
+            vec![
+                PathBuf::from("/home/user/.local/share/Steam"),
+                PathBuf::from("/run/media/mmcblk0p1")
+            ]
+        );
+    }
+
+    #[test]
+    fn wine_prefix_saves() {
+        let root = TempDir::new("discovery");
+        let (home, data) = (root.join("home"), root.join("data"));
+        let prefix = root.join("wine");
+        let lutris = home.join("Games").join("nine-sols");
+        for (prefix, user) in [(&prefix, "alice"), (&lutris, "bob")] {
+            let mut saves = prefix.join("drive_c").join("users").join(user);
+            saves.extend(&["AppData", "LocalLow", "RedCandleGames", "NineSols"]);
+            fs::create_dir_all(saves).unwrap();
+        }
+        fs::create_dir_all(home.join("Games").join("not-a-prefix")).unwrap();
+
+        assert_eq!(
+            wine_prefixes_in(&home, &data, Some(&prefix)),
+            vec![prefix.clone(), lutris.clone()]
+        );
+        assert_eq!(wine_prefixes_in(&home, &data, None), vec![lutris.clone()]);
+
+        let found: Vec<PathBuf> = [&prefix, &lutris]
+            .into_iter()
+            .flat_map(|p| prefix_save_directories(p))
+            .filter(|p| p.is_dir())
+            .collect();
+        assert_eq!(found.len(), 2);
+        assert!(found[0].starts_with(prefix.join("drive_c").join("users").join("alice")));
+        assert!(found[1].starts_with(lutris.join("drive_c").join("users").join("bob")));
+    }
+}
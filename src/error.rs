@@ -2,11 +2,32 @@ use crate::NineSaves;
 use anyhow::Result;
 
 impl NineSaves {
+    /// Reloads the saves and backups. Saves and backups can be added and
+    /// removed from outside the GUI, so the selected external save is
+    /// found again by its path, and the selected backup is cleared.
     pub fn try_refresh(&mut self) {
+        let selected = self
+            .external_selected
+            .and_then(|i| self.data.saves.get(i))
+            .map(|s| s.path.clone());
         let result = self.data.refresh();
         self.handle_error(result);
+        self.external_selected =
+            selected.and_then(|path| self.data.saves.iter().position(|s| s.path == path));
+        self.backup_selected = None;
+        self.show_saves();
     }
-    pub fn handle_error(&mut self, result: Result<()>) {
+    /// Applies the retention policy, unless the operation before it
+    /// failed. That error is the one to show, and it may have left the
+    /// backups in an unexpected state.
+    pub fn try_prune(&mut self) {
+        if self.error_status.is_some() {
+            return;
+        }
+        let result = self.data.prune_backups(false);
+        self.handle_error(result);
+    }
+    pub fn handle_error<T>(&mut self, result: Result<T>) {
         if let Err(e) = result {
             self.error_status = Some(format!("{:?}", e));
         }
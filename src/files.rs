@@ -0,0 +1,146 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Sets the modification time of the file at `path`.
+pub fn set_modified(path: &Path, time: SystemTime) -> Result<()> {
+    File::options()
+        .write(true)
+        .open(path)
+        .and_then(|f| f.set_modified(time))
+        .with_context(|| format!("couldn't set modification time of {:?}", path))
+}
+
+/// The modification time of the file at `path`.
+pub fn modified(path: &Path) -> Result<SystemTime> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .with_context(|| format!("couldn't get modification time of {:?}", path))
+}
+
+/// Copies a file, keeping its modification time and permissions.
+pub fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    let context = || format!("couldn't copy {:?} to {:?}", from, to);
+    let mut source = File::open(from).with_context(context)?;
+    let metadata = source.metadata().with_context(context)?;
+    let mut target = File::create(to).with_context(context)?;
+    io::copy(&mut source, &mut target).with_context(context)?;
+    // The time is set through the handle that wrote the copy, as the
+    // source's permissions may not allow opening it for writing again.
+    metadata
+        .modified()
+        .and_then(|time| target.set_modified(time))
+        .with_context(|| format!("couldn't set modification time of {:?}", to))?;
+    drop(target);
+    fs::set_permissions(to, metadata.permissions())
+        .with_context(|| format!("couldn't set permissions of {:?}", to))
+}
+
+/// Copies everything in the directory `from` into `to`, creating it if
+/// needed.
+pub fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to).with_context(|| format!("couldn't create directory {:?}", to))?;
+    for entry in
+        fs::read_dir(from).with_context(|| format!("couldn't read directory {:?}", from))?
+    {
+        let entry = entry.with_context(|| format!("couldn't read directory {:?}", from))?;
+        let (source, target) = (entry.path(), to.join(entry.file_name()));
+        let file_type = entry
+            .file_type()
+            .with_context(|| format!("couldn't get type of {:?}", source))?;
+        match file_type.is_dir() {
+            true => copy_dir(&source, &target)?,
+            false => copy_file(&source, &target)?,
+        }
+    }
+    Ok(())
+}
+
+/// Removes everything in the directory `dir`, leaving it empty.
+pub fn clear_dir(dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("couldn't read directory {:?}", dir))? {
+        let entry = entry.with_context(|| format!("couldn't read directory {:?}", dir))?;
+        remove_path(&entry.path())?;
+    }
+    Ok(())
+}
+
+/// Removes a file or directory and everything in it, if it exists.
+pub fn remove_path(path: &Path) -> Result<()> {
+    let result = match path.is_dir() {
+        true => fs::remove_dir_all(path),
+        false => fs::remove_file(path),
+    };
+    match result {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("couldn't remove {:?}", path))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn walk_inner(root: &Path, dir: &Path, found: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("couldn't read directory {:?}", dir))? {
+        let entry = entry.with_context(|| format!("couldn't read directory {:?}", dir))?;
+        let path = entry.path();
+        let file_type = entry
+            .file_type()
+            .with_context(|| format!("couldn't get type of {:?}", path))?;
+        if file_type.is_dir() {
+            walk_inner(root, &path, found)?;
+            continue;
+        }
+        let relative = path
+            .strip_prefix(root)
+            .expect("entry is inside root")
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        found.push((relative, path));
+    }
+    Ok(())
+}
+
+/// Whether an entry named `name` is clutter that file managers add, such
+/// as the `.DS_Store` files and `__MACOSX` folders of macOS, or anything
+/// else hidden, rather than part of a save someone meant to share.
+pub fn is_clutter(name: &str) -> bool {
+    name.starts_with('.') || name == "__MACOSX"
+}
+
+/// Every file under `dir`, with its path relative to `dir` using `/` as
+/// the separator.
+pub fn walk(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut found = vec![];
+    walk_inner(dir, dir, &mut found)?;
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn copies_read_only_files() {
+        let root = TempDir::new("files");
+        let (from, to) = (root.join("from"), root.join("to"));
+        fs::create_dir_all(from.join("nested")).unwrap();
+        let file = from.join("nested").join("ro.txt");
+        fs::write(&file, b"read only").unwrap();
+        let old = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        set_modified(&file, old).unwrap();
+        let mut permissions = fs::metadata(&file).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&file, permissions).unwrap();
+
+        copy_dir(&from, &to).unwrap();
+        let copy = to.join("nested").join("ro.txt");
+        assert_eq!(fs::read(&copy).unwrap(), b"read only");
+        assert_eq!(modified(&copy).unwrap(), old);
+        assert!(fs::metadata(&copy).unwrap().permissions().readonly());
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sonic_rs::{JsonContainerTrait, JsonValueTrait, Value};
+use std::collections::BTreeMap;
+
+/// The game's progression flags, decrypted from a save's `flags.txt`.
+///
+/// Flags are keyed the way the game stores them, usually a GUID followed
+/// by the flag's data type.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct SaveFlags {
+    pub flags: BTreeMap<String, Value>,
+}
+
+impl SaveFlags {
+    /// Parses decrypted `flags.txt` contents.
+    pub fn parse(json: &[u8]) -> Result<Self> {
+        sonic_rs::from_slice(json).context("invalid flags json")
+    }
+    /// The raw value stored for `key`.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.flags.get(key)
+    }
+    /// Whether `key` is present and set to a true or nonzero value. Flags
+    /// stored as objects count as set if any of their fields are.
+    pub fn is_set(&self, key: &str) -> bool {
+        self.get(key).is_some_and(value_is_set)
+    }
+    /// Every flag whose key contains `pattern`.
+    pub fn matching<'a>(&'a self, pattern: &'a str) -> impl Iterator<Item = (&'a str, &'a Value)> {
+        self.flags
+            .iter()
+            .filter(move |(k, _)| k.contains(pattern))
+            .map(|(k, v)| (k.as_str(), v))
+    }
+    pub fn len(&self) -> usize {
+        self.flags.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty()
+    }
+}
+
+fn value_is_set(value: &Value) -> bool {
+    if let Some(b) = value.as_bool() {
+        b
+    } else if let Some(n) = value.as_f64() {
+        n != 0.
+    } else if let Some(obj) = value.as_object() {
+        obj.iter().any(|(_, v)| value_is_set(v))
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        let flags =
+            SaveFlags::parse(br#"{"aBool":true,"bInt":0,"cObj":{"currentValue":1},"dStr":"x"}"#)
+                .unwrap();
+        assert_eq!(flags.len(), 4);
+        assert!(flags.is_set("aBool"));
+        assert!(!flags.is_set("bInt"));
+        assert!(flags.is_set("cObj"));
+        assert!(!flags.is_set("dStr"));
+        assert!(!flags.is_set("missing"));
+        assert_eq!(flags.get("dStr").and_then(|v| v.as_str()), Some("x"));
+        assert_eq!(flags.matching("Obj").count(), 1);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::backup::Backup;
+use crate::save::{Save, SavesData};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the journal file in the backups directory.
+const JOURNAL: &str = ".journal.json";
+/// Name the journal is written under before it replaces the old one.
+const JOURNAL_TMP: &str = ".journal.json.tmp";
+/// How many operations are remembered.
+const MAX_ENTRIES: usize = 50;
+
+/// A record of one operation, with enough to reverse it.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct JournalEntry {
+    /// What was done, e.g. `wrote pre-boss to Slot 2`.
+    pub description: String,
+    pub time: DateTime<Utc>,
+    /// The save that was written or deleted.
+    pub path: PathBuf,
+    /// ID of the backup of what was at `path` beforehand. `None` if there
+    /// was nothing there.
+    pub backup: Option<String>,
+}
+
+impl SavesData {
+    fn journal_path(&self) -> PathBuf {
+        self.backups_dir.join(JOURNAL)
+    }
+
+    /// Reads the journal, oldest operation first.
+    pub fn load_journal(&self) -> Result<Vec<JournalEntry>> {
+        let path = self.journal_path();
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        let data = fs::read(&path).with_context(|| format!("couldn't read journal {:?}", path))?;
+        sonic_rs::from_slice(&data).with_context(|| format!("invalid journal {:?}", path))
+    }
+
+    /// Reads the journal, treating one that can't be read as empty. Its
+    /// entries can't be undone anyway, and the next operation recorded
+    /// replaces it.
+    pub(crate) fn journal_or_empty(&self) -> Vec<JournalEntry> {
+        self.load_journal().unwrap_or_default()
+    }
+
+    /// Writes the journal under a temporary name first, so an interrupted
+    /// write leaves the old journal in place rather than a truncated one.
+    fn write_journal(&self, entries: &[JournalEntry]) -> Result<()> {
+        fs::create_dir_all(&self.backups_dir).context("couldn't create backups directory")?;
+        let data = sonic_rs::to_string_pretty(entries).context("couldn't serialize journal")?;
+        let tmp = self.backups_dir.join(JOURNAL_TMP);
+        fs::write(&tmp, data).context("couldn't write journal")?;
+        fs::rename(&tmp, self.journal_path()).context("couldn't replace journal")
+    }
+
+    /// Adds an operation to the journal, forgetting the oldest ones past
+    /// the limit.
+    pub(crate) fn record(
+        &self,
+        description: String,
+        save: &Save,
+        backup: Option<&Backup>,
+    ) -> Result<()> {
+        let mut entries = self.journal_or_empty();
+        entries.push(JournalEntry {
+            description,
+            time: Utc::now(),
+            path: save.path.clone(),
+            backup: backup.map(|b| b.id.clone()),
+        });
+        let excess = entries.len().saturating_sub(MAX_ENTRIES);
+        entries.drain(..excess);
+        self.write_journal(&entries)
+    }
+
+    /// Points the journal entries for the save that was at `from` to `to`,
+    /// after it's been renamed, so undoing them affects the renamed save.
+    pub(crate) fn redirect_journal(&self, from: &Path, to: &Path) -> Result<()> {
+        let mut entries = self.journal_or_empty();
+        if !entries.iter().any(|e| e.path == from) {
+            return Ok(());
+        }
+        for entry in entries.iter_mut().filter(|e| e.path == from) {
+            entry.path = to.to_owned();
+        }
+        self.write_journal(&entries)
+    }
+
+    /// Reverses the most recent operation in the journal, by restoring the
+    /// backup it made, or removing what it created if there was nothing
+    /// there before. Whatever is replaced is backed up first. Returns the
+    /// operation that was undone, if there was one.
+    pub fn undo(&self) -> Result<Option<JournalEntry>> {
+        let mut entries = self.load_journal()?;
+        let Some(entry) = entries.pop() else {
+            return Ok(None);
+        };
+        match &entry.backup {
+            Some(id) => {
+                let backup = Backup::load(&self.backups_dir.join(id)).with_context(|| {
+                    format!(
+                        "the backup made when Nine Saves {}, {}, is gone",
+                        entry.description, id
+                    )
+                })?;
+                self.restore(&backup)?;
+            }
+            None => {
+                let save = self
+                    .slots
+                    .iter()
+                    .chain(&self.saves)
+                    .find(|s| s.path == entry.path)
+                    .with_context(|| format!("{:?} no longer exists", entry.path))?;
+                self.backup_and_delete(save)?;
+                save.delete_dir()?;
+            }
+        }
+        self.write_journal(&entries)?;
+        Ok(Some(entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JOURNAL, JOURNAL_TMP};
+    use crate::backup::Operation;
+    use crate::test_util::{saves_data, write_save, TempDir};
+    use std::fs;
+
+    #[test]
+    fn undo() {
+        let root = TempDir::new("journal");
+        write_save(&root.join("saves").join("a"));
+        let mut data = saves_data(&root);
+        data.refresh().unwrap();
+
+        let (save, slot) = (data.save("a").unwrap(), data.slot(1).unwrap());
+        data.write_to_slot(save, slot).unwrap();
+        data.delete_external(save).unwrap();
+        data.refresh().unwrap();
+        assert!(data.slot(1).unwrap().exists);
+        assert!(data.save("a").is_none());
+
+        let undone = data.undo().unwrap().unwrap();
+        assert_eq!(undone.description, "deleted a");
+        data.refresh().unwrap();
+        assert!(data.save("a").is_some());
+
+        data.undo().unwrap().unwrap();
+        data.refresh().unwrap();
+        assert!(!data.slot(1).unwrap().exists);
+        assert!(data.undo().unwrap().is_none());
+    }
+
+    #[test]
+    fn damaged_journal() {
+        let root = TempDir::new("journal_damaged");
+        write_save(&root.join("saves").join("a"));
+        let mut data = saves_data(&root);
+        fs::write(root.join("backups").join(JOURNAL), b"[{\"descr").unwrap();
+        data.refresh().unwrap();
+        assert!(data.journal.is_empty());
+        assert!(data.journal_error.is_some());
+
+        // The next operation starts a new journal.
+        data.write_to_slot(data.save("a").unwrap(), data.slot(1).unwrap())
+            .unwrap();
+        data.refresh().unwrap();
+        assert_eq!(data.journal.len(), 1);
+        assert!(data.journal_error.is_none());
+        assert!(!root.join("backups").join(JOURNAL_TMP).exists());
+    }
+
+    #[test]
+    fn undo_after_pruning() {
+        let root = TempDir::new("journal_prune");
+        write_save(&root.join("saves").join("a"));
+        let mut data = saves_data(&root);
+        data.retention.keep_last = Some(0);
+        data.refresh().unwrap();
+
+        let save = data.save("a").unwrap();
+        let unjournaled = data.backup(save, Operation::Overwrite).unwrap();
+        data.delete_external(save).unwrap();
+        let pruned = data.prune_backups(false).unwrap();
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].id, unjournaled.id);
+
+        data.refresh().unwrap();
+        data.undo().unwrap().unwrap();
+        data.refresh().unwrap();
+        assert!(data.save("a").is_some());
+    }
+}
@@ -0,0 +1,50 @@
+//! Save management for Nine Sols.
+//!
+//! This crate contains everything Nine Saves does to the game's files,
+//! independent of the iced interface. Build it with
+//! `default-features = false` to use it without pulling in the GUI.
+//!
+//! The entry point is [`SavesData`], which locates the game's slot
+//! directory along with the external saves and backups directories, and
+//! performs the copy, overwrite and delete operations on [`Save`]s.
+//!
+//! ```no_run
+//! use nine_saves::SavesData;
+//!
+//! let mut data = SavesData::new()?;
+//! data.refresh()?;
+//! for save in &data.saves {
+//!     println!("{}", save.name);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod archive;
+pub mod backup;
+pub mod config;
+pub mod decryption;
+pub mod discovery;
+mod files;
+pub mod flags;
+pub mod journal;
+pub mod locations;
+pub mod retention;
+pub mod save;
+pub mod search;
+pub mod sidecar;
+pub mod store;
+#[cfg(test)]
+mod test_util;
+pub mod verify;
+pub mod watch;
+
+pub use backup::{Backup, BackupStorage, Operation};
+pub use config::Config;
+pub use flags::SaveFlags;
+pub use journal::JournalEntry;
+pub use retention::RetentionPolicy;
+pub use save::{Save, SaveInfo, SavesData};
+pub use search::{SaveQuery, SortOrder};
+pub use sidecar::Sidecar;
+pub use verify::Integrity;
+pub use watch::SlotWatcher;
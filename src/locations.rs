@@ -0,0 +1,72 @@
+use crate::save::SaveInfo;
+
+use sonic_rs::JsonValueTrait;
+use std::sync::OnceLock;
+
+/// Each line of `locations.txt` has the form
+/// `<guid>TeleportPointData, <guid>GameLevelMapData = Name`.
+const LOCATIONS: &str = include_str!("../locations.txt");
+
+/// A named place in the game, identified by a teleport point and the scene
+/// it's in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub teleport_point: &'static str,
+    pub scene: &'static str,
+    pub name: &'static str,
+}
+
+fn parse_line(line: &'static str) -> Option<Location> {
+    let (guids, name) = line.split_once('=')?;
+    let (teleport_point, scene) = guids.split_once(',')?;
+    Some(Location {
+        teleport_point: teleport_point.trim(),
+        scene: scene.trim(),
+        name: name.trim(),
+    })
+}
+
+/// Every location known to Nine Saves.
+pub fn locations() -> &'static [Location] {
+    static TABLE: OnceLock<Vec<Location>> = OnceLock::new();
+    TABLE.get_or_init(|| LOCATIONS.lines().filter_map(parse_line).collect())
+}
+
+/// Looks up a location by teleport point GUID, falling back to the scene
+/// GUID when the teleport point isn't known.
+pub fn find_location(teleport_point: Option<&str>, scene: &str) -> Option<&'static Location> {
+    let table = locations();
+    teleport_point
+        .and_then(|t| table.iter().find(|l| l.teleport_point == t))
+        .or_else(|| table.iter().find(|l| l.scene == scene))
+}
+
+impl SaveInfo {
+    /// The name of the place the save is at, if it's in the location table.
+    pub fn location(&self) -> Option<&'static str> {
+        let teleport_point = self.get("lastTeleportPointPath").and_then(|v| v.as_str());
+        find_location(teleport_point, self.atsceneguid()).map(|l| l.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        assert_eq!(
+            find_location(None, "1d24fc87063235c40b83b837dcab39a5GameLevelMapData").map(|l| l.name),
+            Some("Four Seasons Pavilion")
+        );
+        assert_eq!(
+            find_location(
+                Some("2ee85cc763a3d413f9b6e18e7e1fee67TeleportPointData"),
+                "unknown"
+            )
+            .map(|l| l.name),
+            Some("Apeman Facility (Elevator)")
+        );
+        assert!(find_location(None, "unknown").is_none());
+    }
+}
@@ -1,27 +1,32 @@
 #![windows_subsystem = "windows"]
 
-mod decryption;
 mod error;
-mod save;
 
-use save::{Save, SaveInfo, SavesData};
+use nine_saves::sidecar::parse_tags;
+use nine_saves::{Config, SaveQuery, SavesData, SlotWatcher, SortOrder};
 
 use anyhow::Result;
 use iced::alignment::{Horizontal, Vertical};
 use iced::executor;
 use iced::theme;
-use iced::widget::{column, container, radio, row, scrollable, text, Button, TextInput};
+use iced::widget::{
+    checkbox, column, container, pick_list, radio, row, scrollable, text, Button, TextInput,
+};
 use iced::window::icon;
-use iced::Length;
+use iced::{time, Length, Subscription};
 use iced::{
     Application, Background, Border, Color, Command, Element, Padding, Settings, Shadow, Theme,
 };
+use std::path::Path;
 
 const DEBUG: bool = false;
+/// How often to check the game's slots for changes while watching.
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
 
 pub enum AppColor {
     SaveBorder,
     SaveBackground,
+    Error,
 }
 
 impl AppColor {
@@ -29,6 +34,7 @@ impl AppColor {
         match self {
             Self::SaveBorder => Color::from_rgb8(232, 201, 90),
             Self::SaveBackground => Color::from_rgb8(33, 28, 51),
+            Self::Error => Color::from_rgb8(240, 110, 110),
         }
     }
 }
@@ -49,6 +55,32 @@ fn main() -> iced::Result {
     })
 }
 
+/// A choice in the tag filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TagFilter(Option<String>);
+
+impl std::fmt::Display for TagFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some(tag) => write!(f, "#{}", tag),
+            None => f.write_str("All tags"),
+        }
+    }
+}
+
+/// A choice in the game mode filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ModeFilter(Option<u8>);
+
+impl std::fmt::Display for ModeFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(mode) => write!(f, "Mode {}", mode),
+            None => f.write_str("All modes"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Action {
     SaveSlotToNewExternal,
@@ -56,6 +88,11 @@ enum Action {
     WriteSlotToExternal,
     DeleteExternal,
     DeleteSlot,
+    RestoreBackup,
+    RenameExternal,
+    DuplicateExternal,
+    MoveExternal,
+    Import,
 }
 
 #[derive(Debug, Default)]
@@ -63,29 +100,58 @@ struct NineSaves {
     data: SavesData,
     slot_selected: Option<usize>,
     external_selected: Option<usize>,
+    backup_selected: Option<usize>,
     action_selected: Option<Action>,
     new_save_name: String,
+    rename_name: String,
+    duplicate_name: String,
+    move_folder: String,
+    import_path: String,
+    import_name: String,
+    /// Why the last import failed. Usually the path is mistyped or isn't a
+    /// save, which is for the user to fix, so it's shown beside the import
+    /// rather than as an error.
+    import_error: Option<String>,
+    /// The notes and tags being edited for the selected external save.
+    notes: String,
+    tags: String,
+    /// Which external saves are listed, and how.
+    query: SaveQuery,
+    /// Positions in `data.saves` of the listed saves, in order.
+    shown_saves: Vec<usize>,
+    /// Present while the game's slots are being snapshotted automatically.
+    watcher: Option<SlotWatcher>,
+    /// Why the last check of the game's slots failed. It's shown next to
+    /// the watch toggle rather than as an error, since the next check may
+    /// well succeed.
+    watch_error: Option<String>,
     error_status: Option<String>,
 }
 
 impl NineSaves {
     pub fn new() -> Result<Self> {
+        let config = Config::load()?;
+        let data = SavesData::with_config(&config)?;
+        let watcher = match config.watch_slots {
+            true => Some(SlotWatcher::new(&data)?),
+            false => None,
+        };
         Ok(Self {
-            data: SavesData::new()?,
+            data,
+            watcher,
             ..Default::default()
         })
     }
+    /// Updates which external saves are listed, after they or the query
+    /// change.
+    pub fn show_saves(&mut self) {
+        self.shown_saves = self.query.apply(&self.data.saves);
+    }
     pub fn action_ready(&self) -> bool {
         match self.action_selected {
             Some(Action::SaveSlotToNewExternal) => match self.slot_selected {
                 Some(s) => {
-                    !self.new_save_name.is_empty()
-                        && !self
-                            .data
-                            .saves
-                            .iter()
-                            .any(|s| s.name.as_str() == self.new_save_name.as_str())
-                        && self.data.slots[s].exists
+                    self.data.name_available(&self.new_save_name) && self.data.slots[s].exists
                 }
                 None => false,
             },
@@ -101,46 +167,141 @@ impl NineSaves {
                 Some(s) => self.data.slots[s].exists,
                 None => false,
             },
+            Some(Action::RenameExternal) => {
+                self.external_selected.is_some() && self.data.name_available(&self.rename_name)
+            }
+            Some(Action::DuplicateExternal) => {
+                self.external_selected.is_some() && self.data.name_available(&self.duplicate_name)
+            }
+            Some(Action::MoveExternal) => match self.external_selected {
+                Some(s) => self
+                    .data
+                    .name_available(&self.data.saves[s].name_in(&self.move_folder)),
+                None => false,
+            },
+            Some(Action::Import) => {
+                !self.import_path.trim().is_empty() && self.data.name_available(&self.import_name)
+            }
+            Some(Action::RestoreBackup) => self
+                .backup_selected
+                .and_then(|b| self.data.backups.get(b))
+                .is_some_and(|b| b.manifest.is_some()),
             _ => false,
         }
     }
 }
 
-impl SaveInfo {
-    fn formatted_time(&self) -> String {
-        let hours = (self.playtime / 3600.).trunc() as u32;
-        let minutes = ((self.playtime / 60.) % 60.).trunc() as u8;
-        let h_disp = match hours {
-            0 => "".to_string(),
-            _ => format!("{}h ", hours),
-        };
-        let m_disp = format!("{}m", minutes);
-        format!("{}{}", h_disp, m_disp)
-    }
-}
-
 #[derive(Debug, Clone)]
 enum Message {
     SlotPicked(usize),
     SavePicked(usize),
+    BackupPicked(usize),
     ActionPicked(Action),
     NewSaveNameChanged(String),
+    RenameNameChanged(String),
+    NotesChanged(String),
+    TagsChanged(String),
+    SaveNotes,
+    DuplicateNameChanged(String),
+    MoveFolderChanged(String),
+    ImportPathChanged(String),
+    ImportNameChanged(String),
     Refresh,
     PerformAction,
+    Undo,
+    WatchToggled(bool),
+    WatchTick,
+    SearchChanged(String),
+    SortPicked(SortOrder),
+    TagFilterPicked(TagFilter),
+    ModeFilterPicked(ModeFilter),
 }
 
 #[derive(Copy, Clone)]
 enum SaveListKind {
     Slots,
     Saves,
+    Backups,
 }
 
 impl NineSaves {
-    fn action_radio(&self, action: Action) -> Element<Message> {
+    fn action_radio(&self, action: Action) -> Element<'_, Message> {
         radio("", action, self.action_selected, Message::ActionPicked).into()
     }
-    fn save_box(&self, kind: SaveListKind, list: &[Save], i: usize) -> Element<Message> {
-        let save = &list[i];
+    /// The external saves, with a heading before each folder's saves.
+    fn external_save_list(&self) -> Vec<Element<'_, Message>> {
+        let mut list = vec![];
+        let mut previous = None;
+        for &i in &self.shown_saves {
+            let folder = self.data.saves[i].folder();
+            if !folder.is_empty() && previous != Some(folder) {
+                list.push(
+                    container(text(folder).size(18))
+                        .padding(Padding::from([10, 0, 0, 0]))
+                        .into(),
+                );
+            }
+            list.push(self.save_box(SaveListKind::Saves, i));
+            previous = Some(folder);
+        }
+        list
+    }
+
+    /// Search box and sort and filter choices for the external saves.
+    fn save_list_controls(&self) -> Element<'_, Message> {
+        let tags: Vec<TagFilter> = std::iter::once(TagFilter(None))
+            .chain(
+                self.data
+                    .tags()
+                    .into_iter()
+                    .map(|t| TagFilter(Some(t.to_string()))),
+            )
+            .collect();
+        let modes: Vec<ModeFilter> = std::iter::once(ModeFilter(None))
+            .chain(
+                self.data
+                    .game_modes()
+                    .into_iter()
+                    .map(|m| ModeFilter(Some(m))),
+            )
+            .collect();
+        column![
+            TextInput::new("search", &self.query.search).on_input(Message::SearchChanged),
+            row![
+                pick_list(
+                    &SortOrder::ALL[..],
+                    Some(self.query.sort),
+                    Message::SortPicked
+                ),
+                pick_list(
+                    tags,
+                    Some(TagFilter(self.query.tag.clone())),
+                    Message::TagFilterPicked
+                ),
+                pick_list(
+                    modes,
+                    Some(ModeFilter(self.query.game_mode)),
+                    Message::ModeFilterPicked
+                ),
+            ]
+            .spacing(5)
+        ]
+        .spacing(5)
+        .into()
+    }
+
+    fn save_box(&self, kind: SaveListKind, i: usize) -> Element<'_, Message> {
+        let (save, title) = match kind {
+            SaveListKind::Slots => (&self.data.slots[i], self.data.slots[i].name.clone()),
+            SaveListKind::Saves => (
+                &self.data.saves[i],
+                self.data.saves[i].short_name().to_string(),
+            ),
+            SaveListKind::Backups => {
+                let backup = &self.data.backups[i];
+                (&backup.save, backup.describe())
+            }
+        };
         let box_appearance = container::Appearance {
             text_color: Some(Color::WHITE),
             background: Some(Background::Color(AppColor::SaveBackground.color())),
@@ -159,29 +320,56 @@ impl NineSaves {
                 match kind {
                     SaveListKind::Slots => self.slot_selected,
                     SaveListKind::Saves => self.external_selected,
+                    SaveListKind::Backups => self.backup_selected,
                 },
                 match kind {
                     SaveListKind::Slots => Message::SlotPicked,
                     SaveListKind::Saves => Message::SavePicked,
+                    SaveListKind::Backups => Message::BackupPicked,
                 }
             ))
             .center_y()
             .height(Length::Shrink),
             row![
-                container(text(&save.name).size(20)).width(Length::Fill),
+                container(
+                    column![text(title).size(20)]
+                        .push_maybe((!save.sidecar.tags.is_empty()).then(|| {
+                            text(format!("#{}", save.sidecar.tags.join(" #")))
+                                .size(12)
+                                .style(AppColor::SaveBorder.color())
+                        }))
+                        .push_maybe(
+                            (!save.sidecar.notes.is_empty())
+                                .then(|| text(&save.sidecar.notes).size(12))
+                        )
+                )
+                .width(Length::Fill),
                 match info {
-                    Some(info) => container(row![
-                        container(text(format!("Level {}", info.level)))
-                            .width(Length::Fill)
-                            .center_x(),
-                        container(text(info.formatted_time()))
+                    Some(info) => container(column![
+                        row![
+                            container(text(format!("Level {}", info.level())))
+                                .width(Length::Fill)
+                                .center_x(),
+                            container(text(info.formatted_time()))
+                                .width(Length::Fill)
+                                .center_x(),
+                        ],
+                        container(text(info.location().unwrap_or("Unknown location")).size(14))
                             .width(Length::Fill)
                             .center_x(),
                     ])
                     .padding(Padding::from([10, 0, 0, 0])),
-                    None => container(text("empty"))
-                        .padding(Padding::from([0, 10]))
-                        .align_x(Horizontal::Right),
+                    None => match &save.error {
+                        Some(error) => container(column![
+                            text("unreadable").style(AppColor::Error.color()),
+                            text(error).size(12),
+                        ])
+                        .padding(Padding::from([10, 0, 0, 0]))
+                        .width(Length::FillPortion(2)),
+                        None => container(text("empty"))
+                            .padding(Padding::from([0, 10]))
+                            .align_x(Horizontal::Right),
+                    },
                 }
             ],
         ])
@@ -191,7 +379,7 @@ impl NineSaves {
         .width(Length::Fill)
         .into()
     }
-    fn selected_slot_display(&self) -> Element<Message> {
+    fn selected_slot_display(&self) -> Element<'_, Message> {
         container(match self.slot_selected {
             Some(slot) => &self.data.slots[slot].name,
             None => "selected slot",
@@ -199,7 +387,7 @@ impl NineSaves {
         .style(theme::Container::Box)
         .into()
     }
-    fn selected_save_display(&self) -> Element<Message> {
+    fn selected_save_display(&self) -> Element<'_, Message> {
         container(match self.external_selected {
             Some(slot) => &self.data.saves[slot].name,
             None => "selected save",
@@ -207,6 +395,16 @@ impl NineSaves {
         .style(theme::Container::Box)
         .into()
     }
+    fn selected_backup_display(&self) -> Element<'_, Message> {
+        container(text(
+            match self.backup_selected.and_then(|b| self.data.backups.get(b)) {
+                Some(backup) => backup.describe(),
+                None => "selected backup".to_string(),
+            },
+        ))
+        .style(theme::Container::Box)
+        .into()
+    }
 }
 
 impl Application for NineSaves {
@@ -236,62 +434,170 @@ impl Application for NineSaves {
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
         match message {
             Message::SlotPicked(i) => self.slot_selected = Some(i),
-            Message::SavePicked(i) => self.external_selected = Some(i),
+            Message::SavePicked(i) => {
+                self.external_selected = Some(i);
+                self.move_folder = self.data.saves[i].folder().to_string();
+                let sidecar = &self.data.saves[i].sidecar;
+                self.notes = sidecar.notes.clone();
+                self.tags = sidecar.tags.join(", ");
+            }
+            Message::SearchChanged(s) => {
+                self.query.search = s;
+                self.show_saves();
+            }
+            Message::SortPicked(sort) => {
+                self.query.sort = sort;
+                self.show_saves();
+            }
+            Message::TagFilterPicked(TagFilter(tag)) => {
+                self.query.tag = tag;
+                self.show_saves();
+            }
+            Message::ModeFilterPicked(ModeFilter(mode)) => {
+                self.query.game_mode = mode;
+                self.show_saves();
+            }
+            Message::NotesChanged(s) => self.notes = s,
+            Message::TagsChanged(s) => self.tags = s,
+            Message::SaveNotes => {
+                if let Some(i) = self.external_selected {
+                    let res = self.data.saves[i].set_notes(&self.notes, parse_tags(&self.tags));
+                    self.handle_error(res);
+                    self.try_refresh();
+                }
+            }
+            Message::BackupPicked(i) => self.backup_selected = Some(i),
             Message::ActionPicked(action) => self.action_selected = Some(action),
             Message::NewSaveNameChanged(s) => self.new_save_name = s.clone(),
+            Message::RenameNameChanged(s) => self.rename_name = s.clone(),
+            Message::DuplicateNameChanged(s) => self.duplicate_name = s.clone(),
+            Message::MoveFolderChanged(s) => self.move_folder = s.clone(),
+            Message::ImportPathChanged(s) => {
+                // Name the save after what's being imported, unless a name
+                // was typed in.
+                let stem = |path: &str| {
+                    Path::new(path.trim())
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                };
+                if self.import_name == stem(&self.import_path) {
+                    self.import_name = stem(&s);
+                }
+                self.import_path = s;
+                self.import_error = None;
+            }
+            Message::ImportNameChanged(s) => {
+                self.import_name = s;
+                self.import_error = None;
+            }
             Message::Refresh => self.try_refresh(),
-            Message::PerformAction => match self.action_selected {
-                Some(Action::SaveSlotToNewExternal) => {
-                    let destination = self.data.external_saves_dir.join(&self.new_save_name);
-                    let res =
-                        self.data.slots[self.slot_selected.expect("must exist")].copy(&destination);
-                    self.handle_error(res);
-                    self.try_refresh();
+            Message::Undo => {
+                let res = self.data.undo();
+                self.handle_error(res);
+                self.slot_selected = None;
+                self.external_selected = None;
+                self.backup_selected = None;
+                self.try_prune();
+                self.try_refresh();
+            }
+            Message::WatchToggled(on) => match on {
+                true => match SlotWatcher::new(&self.data) {
+                    Ok(watcher) => {
+                        self.watcher = Some(watcher);
+                        self.watch_error = None;
+                    }
+                    Err(e) => self.watch_error = Some(format!("{:#}", e)),
+                },
+                false => {
+                    self.watcher = None;
+                    self.watch_error = None;
+                }
+            },
+            Message::WatchTick => {
+                if let Some(watcher) = &mut self.watcher {
+                    match watcher.poll(&self.data) {
+                        Ok(snapshots) => {
+                            self.watch_error = None;
+                            if !snapshots.is_empty() {
+                                self.try_prune();
+                                self.try_refresh();
+                            }
+                        }
+                        Err(e) => self.watch_error = Some(format!("{:#}", e)),
+                    }
                 }
-                Some(Action::WriteExternalToSlot) => {
-                    let slot = &self.data.slots[self.slot_selected.expect("must exist")].clone();
-                    let source =
-                        &self.data.saves[self.external_selected.expect("must exist")].clone();
-                    if slot.exists {
-                        let res = self.data.backup_and_overwrite(source, slot);
+            }
+            Message::PerformAction => {
+                match self.action_selected {
+                    Some(Action::SaveSlotToNewExternal) => {
+                        let slot = &self.data.slots[self.slot_selected.expect("must exist")];
+                        let res = self.data.save_slot_to_new(slot, &self.new_save_name);
                         self.handle_error(res);
-                    } else {
-                        let res = slot.create_dir();
+                    }
+                    Some(Action::WriteExternalToSlot) => {
+                        let slot = &self.data.slots[self.slot_selected.expect("must exist")];
+                        let source = &self.data.saves[self.external_selected.expect("must exist")];
+                        let res = self.data.write_to_slot(source, slot);
                         self.handle_error(res);
-                        let res = source.copy(&slot.path);
+                    }
+                    Some(Action::WriteSlotToExternal) => {
+                        let slot = &self.data.slots[self.slot_selected.expect("must exist")];
+                        let save = &self.data.saves[self.external_selected.expect("must exist")];
+                        let res = self.data.write_slot_to_external(slot, save);
                         self.handle_error(res);
                     }
-                    self.try_refresh();
-                }
-                Some(Action::WriteSlotToExternal) => {
-                    let slot = &self.data.slots[self.slot_selected.expect("must exist")];
-                    let save = &self.data.saves[self.external_selected.expect("must exist")];
-                    let res = self.data.backup_and_overwrite(slot, save);
-                    self.handle_error(res);
-                    self.try_refresh();
-                }
-                Some(Action::DeleteExternal) => {
-                    let save = self.data.saves[self.external_selected.expect("must exist")].clone();
-                    let res = self.data.backup_and_delete(&save);
-                    self.handle_error(res);
-                    let res = save.delete_dir();
-                    self.handle_error(res);
-                    self.external_selected = None;
-                    self.try_refresh();
-                }
-                Some(Action::DeleteSlot) => {
-                    let slot = self.data.slots[self.slot_selected.expect("must exist")].clone();
-                    let res = self.data.backup_and_delete(&slot);
-                    self.handle_error(res);
-                    self.try_refresh();
+                    Some(Action::DeleteExternal) => {
+                        let save = &self.data.saves[self.external_selected.expect("must exist")];
+                        let res = self.data.delete_external(save);
+                        self.handle_error(res);
+                        self.external_selected = None;
+                    }
+                    Some(Action::DeleteSlot) => {
+                        let slot = &self.data.slots[self.slot_selected.expect("must exist")];
+                        let res = self.data.delete_slot(slot);
+                        self.handle_error(res);
+                    }
+                    Some(Action::RenameExternal) => {
+                        let save = &self.data.saves[self.external_selected.expect("must exist")];
+                        let res = self.data.rename_external(save, &self.rename_name);
+                        self.handle_error(res);
+                        self.external_selected = None;
+                    }
+                    Some(Action::DuplicateExternal) => {
+                        let save = &self.data.saves[self.external_selected.expect("must exist")];
+                        let res = self.data.duplicate_external(save, &self.duplicate_name);
+                        self.handle_error(res);
+                        self.external_selected = None;
+                    }
+                    Some(Action::MoveExternal) => {
+                        let save = &self.data.saves[self.external_selected.expect("must exist")];
+                        let res = self.data.move_external(save, &self.move_folder);
+                        self.handle_error(res);
+                        self.external_selected = None;
+                    }
+                    Some(Action::Import) => {
+                        let res = self
+                            .data
+                            .import(Path::new(self.import_path.trim()), &self.import_name);
+                        self.import_error = res.err().map(|e| format!("{:#}", e));
+                        self.external_selected = None;
+                    }
+                    Some(Action::RestoreBackup) => {
+                        let backup = &self.data.backups[self.backup_selected.expect("must exist")];
+                        let res = self.data.restore(backup);
+                        self.handle_error(res);
+                    }
+                    None => (),
                 }
-                None => (),
-            },
+                self.try_prune();
+                self.try_refresh();
+            }
         };
         Command::none()
     }
 
-    fn view(&self) -> Element<Self::Message> {
+    fn view(&self) -> Element<'_, Self::Message> {
         let content = match &self.error_status {
             Some(e) => container(column![
                 text("Nine Saves encountered an error").size(20),
@@ -306,9 +612,10 @@ impl Application for NineSaves {
                         .width(Length::Fill)
                         .padding(10),
                     container(scrollable(
-                        column(self.data.slots.iter().enumerate().map(|(i, _)| {
-                            self.save_box(SaveListKind::Slots, &self.data.slots, i)
-                        }))
+                        column(
+                            (0..self.data.slots.len())
+                                .map(|i| self.save_box(SaveListKind::Slots, i))
+                        )
                         .spacing(5)
                     ))
                     .height(Length::Shrink),
@@ -320,10 +627,21 @@ impl Application for NineSaves {
                         .center_x()
                         .width(Length::Fill)
                         .padding(10),
+                    self.save_list_controls(),
+                    scrollable(column(self.external_save_list()).spacing(5)).height(Length::Fill)
+                ]
+                .into();
+
+                let backups: Element<_> = column![
+                    container(text("Backups").size(25))
+                        .center_x()
+                        .width(Length::Fill)
+                        .padding(10),
                     scrollable(
-                        column(self.data.saves.iter().enumerate().map(|(i, _)| {
-                            self.save_box(SaveListKind::Saves, &self.data.saves, i)
-                        }))
+                        column(
+                            (0..self.data.backups.len())
+                                .map(|i| self.save_box(SaveListKind::Backups, i))
+                        )
                         .spacing(5)
                     )
                     .height(Length::Fill)
@@ -374,6 +692,95 @@ impl Application for NineSaves {
                     row![text("Delete "), self.selected_save_display(),]
                 ];
 
+                let rename_external = row![
+                    self.action_radio(Action::RenameExternal),
+                    row![
+                        text("Rename "),
+                        self.selected_save_display(),
+                        text(" to "),
+                        container(
+                            TextInput::new("save name", &self.rename_name)
+                                .on_input(Message::RenameNameChanged)
+                        )
+                        .max_width(100)
+                    ]
+                ];
+
+                let duplicate_external = row![
+                    self.action_radio(Action::DuplicateExternal),
+                    row![
+                        text("Duplicate "),
+                        self.selected_save_display(),
+                        text(" as "),
+                        container(
+                            TextInput::new("save name", &self.duplicate_name)
+                                .on_input(Message::DuplicateNameChanged)
+                        )
+                        .max_width(100)
+                    ]
+                ];
+
+                let move_external = row![
+                    self.action_radio(Action::MoveExternal),
+                    row![
+                        text("Move "),
+                        self.selected_save_display(),
+                        text(" to folder "),
+                        container(
+                            TextInput::new("none", &self.move_folder)
+                                .on_input(Message::MoveFolderChanged)
+                        )
+                        .max_width(100)
+                    ]
+                ];
+
+                let import = row![
+                    self.action_radio(Action::Import),
+                    row![
+                        text("Import "),
+                        container(
+                            TextInput::new("directory or zip", &self.import_path)
+                                .on_input(Message::ImportPathChanged)
+                        )
+                        .max_width(200),
+                        text(" as "),
+                        container(
+                            TextInput::new("save name", &self.import_name)
+                                .on_input(Message::ImportNameChanged)
+                        )
+                        .max_width(100),
+                        text(self.import_error.as_deref().unwrap_or(""))
+                            .size(12)
+                            .style(AppColor::Error.color()),
+                    ]
+                ];
+
+                let restore_backup = row![
+                    self.action_radio(Action::RestoreBackup),
+                    row![
+                        text("Restore "),
+                        self.selected_backup_display(),
+                        text(" to where it came from"),
+                    ]
+                ];
+
+                let notes = row![
+                    text("Notes for "),
+                    self.selected_save_display(),
+                    text(" "),
+                    TextInput::new("notes", &self.notes).on_input(Message::NotesChanged),
+                    text(" tags "),
+                    container(
+                        TextInput::new("comma, separated", &self.tags)
+                            .on_input(Message::TagsChanged)
+                    )
+                    .max_width(200),
+                    text(" "),
+                    Button::new("Save Notes")
+                        .on_press_maybe(self.external_selected.map(|_| Message::SaveNotes)),
+                ]
+                .align_items(iced::Alignment::Center);
+
                 let actions: iced::widget::Container<Message> = container(column![
                     container(text("Actions").size(25))
                         .center_x()
@@ -381,17 +788,59 @@ impl Application for NineSaves {
                         .width(Length::Fill),
                     row![
                         container(
-                            column![save_slot_to_external, write_slot_to_external, delete_slot]
-                                .spacing(5)
+                            column![
+                                save_slot_to_external,
+                                write_slot_to_external,
+                                delete_slot,
+                                rename_external,
+                                move_external
+                            ]
+                            .spacing(5)
                         )
                         .width(Length::Fill),
-                        container(column![write_external_to_slot, delete_external].spacing(5))
-                            .width(Length::Fill)
+                        container(
+                            column![
+                                write_external_to_slot,
+                                delete_external,
+                                duplicate_external,
+                                restore_backup,
+                                import
+                            ]
+                            .spacing(5)
+                        )
+                        .width(Length::Fill)
                     ]
                     .spacing(20),
+                    container(notes).padding(Padding::from([10, 0, 0, 0])),
                     row![
-                        container(Button::new("Refresh").on_press(Message::Refresh))
-                            .align_x(Horizontal::Left),
+                        container(
+                            row![
+                                Button::new("Refresh").on_press(Message::Refresh),
+                                match self.data.journal.last() {
+                                    Some(entry) =>
+                                        Button::new(text(format!("Undo: {}", entry.description)))
+                                            .on_press(Message::Undo),
+                                    None => Button::new("Undo"),
+                                },
+                                text(match &self.data.journal_error {
+                                    Some(e) => format!("Nothing can be undone: {}", e),
+                                    None => String::new(),
+                                })
+                                .size(12)
+                                .style(AppColor::Error.color()),
+                                checkbox(
+                                    "Snapshot slots when the game saves",
+                                    self.watcher.is_some()
+                                )
+                                .on_toggle(Message::WatchToggled),
+                                text(self.watch_error.as_deref().unwrap_or(""))
+                                    .size(12)
+                                    .style(AppColor::Error.color()),
+                            ]
+                            .spacing(20)
+                            .align_items(iced::Alignment::Center)
+                        )
+                        .align_x(Horizontal::Left),
                         container({
                             let button = Button::new("Perform Action");
                             match self.action_ready() {
@@ -416,7 +865,7 @@ impl Application for NineSaves {
                         .center_x()
                         .align_y(Vertical::Top)
                         .width(Length::Fill),
-                    container(row![game_slots, external_saves].spacing(40))
+                    container(row![game_slots, external_saves, backups].spacing(40))
                         .height(Length::FillPortion(2)),
                     actions
                 ])
@@ -432,6 +881,12 @@ impl Application for NineSaves {
             content
         }
     }
+    fn subscription(&self) -> Subscription<Self::Message> {
+        match self.watcher {
+            Some(_) => time::every(WATCH_INTERVAL).map(|_| Message::WatchTick),
+            None => Subscription::none(),
+        }
+    }
     fn theme(&self) -> Theme {
         Theme::TokyoNight
     }
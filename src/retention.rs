@@ -0,0 +1,253 @@
+use crate::backup::{backups_from_dir, Backup, Operation};
+use crate::save::SavesData;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How many snapshots of each slot are kept when `keep_snapshots` isn't set.
+pub const DEFAULT_KEEP_SNAPSHOTS: usize = 20;
+
+/// Rules for which backups to keep. With nothing set, every backup is kept,
+/// apart from automatic snapshots.
+///
+/// A backup survives if it is one of the `keep_last` newest backups of its
+/// save, or is younger than `keep_days`. Snapshots made by watch mode are
+/// counted separately, and only the `keep_snapshots` newest of each slot
+/// survive. After that, the oldest backups are removed until the total is
+/// under `max_size_mb`, though the newest backup and snapshot of each save
+/// are never removed. Backups made by older versions of Nine Saves, which
+/// don't record their origin or time, are never pruned.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_last: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_days: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size_mb: Option<u64>,
+    /// Defaults to [`DEFAULT_KEEP_SNAPSHOTS`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_snapshots: Option<usize>,
+}
+
+impl RetentionPolicy {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Picks which of `backups` to remove, given each one's size in bytes.
+    /// `backups` must be sorted newest first.
+    pub fn select(&self, backups: &[(&Backup, u64)], now: DateTime<Utc>) -> Vec<usize> {
+        let mut remove = vec![false; backups.len()];
+        // Position of each backup among those from the same save, with
+        // snapshots ranked apart from other backups.
+        let mut rank = vec![0; backups.len()];
+        let mut seen: HashMap<(&PathBuf, bool), usize> = HashMap::new();
+        for (i, (backup, _)) in backups.iter().enumerate() {
+            if let Some(m) = &backup.manifest {
+                let snapshot = m.operation == Operation::Snapshot;
+                let count = seen.entry((&m.source_path, snapshot)).or_default();
+                rank[i] = *count;
+                *count += 1;
+            }
+        }
+
+        let keep_snapshots = self.keep_snapshots.unwrap_or(DEFAULT_KEEP_SNAPSHOTS);
+        for (i, (backup, _)) in backups.iter().enumerate() {
+            if backup
+                .manifest
+                .as_ref()
+                .is_some_and(|m| m.operation == Operation::Snapshot)
+            {
+                remove[i] = rank[i] >= keep_snapshots;
+            }
+        }
+
+        if self.keep_last.is_some() || self.keep_days.is_some() {
+            for (i, (backup, _)) in backups.iter().enumerate() {
+                let Some(m) = &backup.manifest else {
+                    continue;
+                };
+                if m.operation == Operation::Snapshot {
+                    continue;
+                }
+                let recent_enough = self.keep_last.is_some_and(|n| rank[i] < n);
+                // Too many days to count is as good as forever.
+                let young_enough = self.keep_days.is_some_and(|days| {
+                    i64::try_from(days)
+                        .ok()
+                        .and_then(Duration::try_days)
+                        .is_none_or(|days| now - m.time < days)
+                });
+                remove[i] = !recent_enough && !young_enough;
+            }
+        }
+
+        if let Some(max_mb) = self.max_size_mb {
+            let max = max_mb.saturating_mul(1024 * 1024);
+            let mut total: u64 = backups
+                .iter()
+                .zip(&remove)
+                .filter(|(_, removed)| !**removed)
+                .map(|((_, size), _)| size)
+                .sum();
+            for i in (0..backups.len()).rev() {
+                if total <= max {
+                    break;
+                }
+                if !remove[i] && rank[i] > 0 && backups[i].0.manifest.is_some() {
+                    remove[i] = true;
+                    total -= backups[i].1;
+                }
+            }
+        }
+
+        (0..backups.len()).filter(|i| remove[*i]).collect()
+    }
+}
+
+/// Total size in bytes of the files under `path`.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| match e.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&e.path()),
+            _ => e.metadata().map(|m| m.len()).unwrap_or(0),
+        })
+        .sum()
+}
+
+impl SavesData {
+    /// Removes the backups that `self.retention` doesn't keep, and returns
+    /// them. With `dry_run`, nothing is removed. Backups the journal refers
+    /// to are always kept, so that [`SavesData::undo`] keeps working. A
+    /// journal that can't be read refers to none.
+    pub fn prune_backups(&self, dry_run: bool) -> Result<Vec<Backup>> {
+        let backups = backups_from_dir(&self.backups_dir).context("failed to load backups")?;
+        let journal = self.journal_or_empty();
+        let undoable: HashSet<&String> = journal.iter().filter_map(|e| e.backup.as_ref()).collect();
+        let store = self.store();
+        let sized: Vec<(&Backup, u64)> = backups
+            .iter()
+            .map(
+                |b| match b.manifest.as_ref().and_then(|m| m.files.as_ref()) {
+                    Some(files) => (b, store.size(files)),
+                    None => (b, dir_size(&b.path)),
+                },
+            )
+            .collect();
+        let selected = self.retention.select(&sized, Utc::now());
+        let pruned: Vec<Backup> = selected
+            .into_iter()
+            .map(|i| backups[i].clone())
+            .filter(|b| !undoable.contains(&b.id))
+            .collect();
+        if !dry_run {
+            for backup in &pruned {
+                fs::remove_dir_all(&backup.path)
+                    .with_context(|| format!("couldn't remove backup {}", backup.id))?;
+            }
+            // Unreadable backups may use any stored file, so the store is
+            // left alone until they're dealt with.
+            if !pruned.is_empty() && backups.iter().all(|b| b.error.is_none()) {
+                self.collect_garbage(false)?;
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::{BackupManifest, Operation};
+    use crate::test_util::save;
+
+    fn backup(source: &str, days_old: i64, now: DateTime<Utc>) -> Backup {
+        let path = PathBuf::from(source);
+        Backup {
+            id: format!("{}_{}", days_old, source),
+            path: path.clone(),
+            manifest: Some(BackupManifest {
+                source_name: source.to_string(),
+                source_path: path.clone(),
+                operation: Operation::Overwrite,
+                time: now - Duration::days(days_old),
+                info: None,
+                error: None,
+                sidecar: None,
+                files: None,
+            }),
+            error: None,
+            save: save(path),
+        }
+    }
+
+    #[test]
+    fn selection() {
+        let now = Utc::now();
+        let backups = [
+            backup("a", 0, now),
+            backup("b", 1, now),
+            backup("a", 2, now),
+            backup("a", 10, now),
+            backup("b", 20, now),
+        ];
+        let sized: Vec<(&Backup, u64)> = backups.iter().map(|b| (b, 1024 * 1024)).collect();
+
+        let keep_one = RetentionPolicy {
+            keep_last: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(keep_one.select(&sized, now), vec![2, 3, 4]);
+
+        let keep_week = RetentionPolicy {
+            keep_last: Some(1),
+            keep_days: Some(7),
+            ..Default::default()
+        };
+        assert_eq!(keep_week.select(&sized, now), vec![3, 4]);
+
+        let small = RetentionPolicy {
+            max_size_mb: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(small.select(&sized, now), vec![3, 4]);
+
+        assert!(RetentionPolicy::default().select(&sized, now).is_empty());
+
+        let huge = RetentionPolicy {
+            keep_days: Some(u64::MAX),
+            max_size_mb: Some(u64::MAX),
+            ..Default::default()
+        };
+        assert!(huge.select(&sized, now).is_empty());
+
+        let snapshots: Vec<Backup> = (0..3)
+            .map(|days_old| {
+                let mut snapshot = backup("a", days_old, now);
+                snapshot.manifest.as_mut().unwrap().operation = Operation::Snapshot;
+                snapshot
+            })
+            .collect();
+        let mixed: Vec<(&Backup, u64)> = sized
+            .iter()
+            .copied()
+            .chain(snapshots.iter().map(|b| (b, 1024 * 1024)))
+            .collect();
+        let few_snapshots = RetentionPolicy {
+            keep_last: Some(1),
+            keep_snapshots: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(few_snapshots.select(&mixed, now), vec![2, 3, 4, 6, 7]);
+    }
+}
@@ -1,58 +1,93 @@
+use crate::archive;
+use crate::backup::{backups_from_dir, Backup, BackupStorage, Operation};
+use crate::config::Config;
+use crate::discovery::save_directory;
+use crate::files;
+use crate::journal::JournalEntry;
+use crate::retention::RetentionPolicy;
+use crate::sidecar::{Sidecar, SIDECAR_SUFFIX};
+
 use anyhow::{anyhow, Context, Result};
 use directories::BaseDirs;
 use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use sonic_rs::{JsonValueMutTrait, JsonValueTrait, Value};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-const NS_ID: &str = "1809540";
-
-fn save_directory(base_dirs: &BaseDirs) -> Result<PathBuf> {
-    let path = if cfg!(target_os = "windows") {
-        let mut path = base_dirs.home_dir().to_owned();
-        path.extend(&["AppData", "LocalLow", "RedCandleGames", "NineSols"]);
-        path
-    } else if cfg!(target_os = "macos") {
-        let mut path = base_dirs.data_dir().to_owned();
-        path.extend(&["RedCandleGames", "NineSols"]);
-        path
-    } else if cfg!(target_os = "linux") {
-        let data_path = base_dirs.data_dir().join("Steam");
-        let mut path = match data_path.exists() {
-            true => data_path.to_owned(),
-            false => base_dirs.home_dir().join(".steam").join("steam").to_owned(),
-        };
-        path.extend(&[
-            "steamapps",
-            "compatdata",
-            NS_ID,
-            "pfx",
-            "drive_c",
-            "users",
-            "steamuser",
-            "AppData",
-            "LocalLow",
-            "RedCandleGames",
-            "NineSols",
-        ]);
-        path
-    } else {
-        panic!("OS not supported.")
-    };
-
-    match path.exists() {
-        true => Ok(path),
-        false => Err(anyhow!("Could not find Nine Sols save directory at {}. Please report this bug along with the path that your saves are actually stored.", path.display()))
+/// The game slot at `path`, if it is one of the game's `saveslotN`
+/// directories. Its info isn't read.
+pub(crate) fn game_slot(path: &Path) -> Option<Save> {
+    let re = Regex::new("^saveslot([0-3])(_BeforeNoReturnPoint)?$").expect("valid regex");
+    let caps = re.captures(path.file_name()?.to_str()?)?;
+    let num: u8 = caps[1].parse().expect("regex only matches digits");
+    let nrp_backup = caps.get(2).is_some();
+    Some(Save {
+        name: match nrp_backup {
+            true => format!("Slot {} (Before NRP)", num + 1),
+            false => format!("Slot {}", num + 1),
+        },
+        path: path.to_owned(),
+        nrp_backup,
+        exists: true,
+        info: None,
+        error: None,
+        sidecar: Sidecar::default(),
+    })
+}
+
+/// Whether the directory `dir` is certainly a save, even a damaged one: it
+/// has the game's metadata, or Nine Saves' notes and tags.
+fn is_save_dir(dir: &Path) -> bool {
+    let mut sidecar = dir.as_os_str().to_owned();
+    sidecar.push(SIDECAR_SUFFIX);
+    dir.join("meta.txt").exists() || Path::new(&sidecar).exists()
+}
+
+/// Whether `dir` holds any saves, directly or in folders.
+fn contains_saves(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|entries| {
+        entries
+            .filter_map(|x| x.ok())
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .any(|entry| {
+                let path = entry.path();
+                match path.is_dir() {
+                    true => is_save_dir(&path) || contains_saves(&path),
+                    false => archive::is_archive(&path),
+                }
+            })
+    })
+}
+
+/// Whether `dir` is a folder of saves rather than a save itself. Anything
+/// that isn't clearly a folder is listed as a save, so that empty or
+/// damaged saves don't disappear.
+fn is_folder(dir: &Path) -> bool {
+    !is_save_dir(dir) && contains_saves(dir)
+}
+
+/// The only thing in `dir` besides clutter, if that's a directory without
+/// a `meta.txt` beside it.
+fn only_folder(dir: &Path) -> Option<PathBuf> {
+    if dir.join("meta.txt").exists() {
+        return None;
     }
+    let mut entries = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|x| x.ok())
+        .filter(|e| !files::is_clutter(&e.file_name().to_string_lossy()));
+    let only = entries.next()?.path();
+    (entries.next().is_none() && only.is_dir()).then_some(only)
 }
 
-fn saves_from_dir(dir: &Path) -> Result<Vec<Save>> {
-    fs::read_dir(dir)
-        .context("couldn't read external saves directory")?
-        .filter_map(|x| match x {
-            Ok(x) => Some(x),
-            Err(_) => None,
-        })
+/// Adds the saves in `dir` to `saves`, going into folders. Their names are
+/// prefixed with `folder`.
+fn collect_saves(dir: &Path, folder: &str, saves: &mut Vec<Save>) -> Result<()> {
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("couldn't read external saves directory {:?}", dir))?
+        .filter_map(|x| x.ok())
         .filter_map(|p| {
             let name = p.file_name().into_string();
             match name {
@@ -60,144 +95,396 @@ fn saves_from_dir(dir: &Path) -> Result<Vec<Save>> {
                 Err(_) => None,
             }
         })
-        .filter_map(|(name, path)| match path.is_dir() {
-            true => Some(Save {
-                name,
-                path,
-                nrp_backup: false,
-                exists: true,
-                info: None,
-            }),
-            false => None,
-        })
-        .map(|s| {
-            s.clone()
-                .with_decrypted_info()
-                .with_context(|| format!("couldn't decrypt info for save {:?}", &s))
-        })
-        .collect()
+        // Hidden entries are Nine Saves' own temporary files.
+        .filter(|(name, _)| !name.starts_with('.'));
+    for (name, path) in entries {
+        let name = match (path.is_dir(), archive::is_archive(&path) && path.is_file()) {
+            (true, _) if is_folder(&path) => {
+                collect_saves(&path, &format!("{}{}/", folder, name), saves)?;
+                continue;
+            }
+            (true, _) => name,
+            (false, true) => match path.file_stem().and_then(|s| s.to_str()) {
+                Some(stem) => stem.to_string(),
+                None => continue,
+            },
+            (false, false) => continue,
+        };
+        saves.push(Save {
+            name: format!("{}{}", folder, name),
+            path,
+            nrp_backup: false,
+            exists: true,
+            info: None,
+            error: None,
+            sidecar: Sidecar::default(),
+        });
+    }
+    Ok(())
+}
+
+/// The saves in `dir` and the folders under it. Saves in folders are named
+/// by their path, e.g. `bosses/pre-Yingzhao`.
+fn saves_from_dir(dir: &Path) -> Result<Vec<Save>> {
+    let mut saves = vec![];
+    collect_saves(dir, "", &mut saves)?;
+    Ok(saves
+        .into_iter()
+        .map(Save::with_info_or_error)
+        .map(Save::with_sidecar)
+        .collect())
 }
 
 fn data_dir(base_dirs: &BaseDirs) -> Result<PathBuf> {
     Ok(base_dirs.data_dir().join("nine_saves"))
 }
 
+/// A single save directory: one of the game's slots, an external save, or
+/// a backup.
 #[derive(Clone, Debug)]
 pub struct Save {
+    /// Display name. For external saves, this is the directory name,
+    /// preceded by any folders it's in, e.g. `bosses/pre-Yingzhao`. For
+    /// backups, it's the name of the save that was backed up.
     pub name: String,
     pub path: PathBuf,
+    /// Whether this is the game's "Before No Return Point" copy of a slot.
     pub nrp_backup: bool,
+    /// Whether the directory exists. Empty game slots don't.
     pub exists: bool,
+    /// Decrypted contents of `meta.txt`, if it has been read.
     pub info: Option<SaveInfo>,
+    /// Why `meta.txt` couldn't be read, for saves that are damaged or
+    /// weren't written by the game. These can still be copied, backed up
+    /// and deleted.
+    pub error: Option<String>,
+    /// Notes and checksums, for external saves. Empty for anything else.
+    pub sidecar: Sidecar,
 }
 
+/// The metadata the game stores in a save's `meta.txt`.
+///
+/// The whole object is kept as the game wrote it, fields in the same
+/// order, so writing it back only changes what was [`set`](Self::set).
+/// The fields Nine Saves uses are checked and read when it's parsed.
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "Value", into = "Value")]
 pub struct SaveInfo {
-    pub level: u8,
-    #[serde(rename(deserialize = "playTime"))]
-    pub playtime: f64,
-    pub gold: u32,
-    #[serde(rename(deserialize = "gameMode"))]
-    pub gamemode: u8,
-    #[serde(rename(deserialize = "atSceneGuid"))]
-    pub atsceneguid: String,
+    level: u8,
+    playtime: f64,
+    gold: u32,
+    gamemode: u8,
+    atsceneguid: String,
+    json: Value,
+}
+
+/// Reads the field `key` of the metadata object `json`.
+fn field<T: DeserializeOwned>(json: &Value, key: &str) -> Result<T> {
+    let value = json
+        .get(key)
+        .with_context(|| format!("metadata has no {:?}", key))?;
+    sonic_rs::from_value(value).with_context(|| format!("invalid {:?} in metadata", key))
+}
+
+impl TryFrom<Value> for SaveInfo {
+    type Error = anyhow::Error;
+
+    fn try_from(json: Value) -> Result<Self> {
+        if !json.is_object() {
+            return Err(anyhow!("metadata isn't a json object"));
+        }
+        Ok(SaveInfo {
+            level: field(&json, "level")?,
+            playtime: field(&json, "playTime")?,
+            gold: field(&json, "gold")?,
+            gamemode: field(&json, "gameMode")?,
+            atsceneguid: field(&json, "atSceneGuid")?,
+            json,
+        })
+    }
+}
+
+impl From<SaveInfo> for Value {
+    fn from(info: SaveInfo) -> Self {
+        info.json
+    }
 }
 
+/// The locations Nine Saves works with and the saves found in them.
 #[derive(Debug, Default)]
 pub struct SavesData {
     pub game_slots_dir: PathBuf,
     pub external_saves_dir: PathBuf,
     pub backups_dir: PathBuf,
+    pub retention: RetentionPolicy,
+    pub backup_storage: BackupStorage,
+    /// Whether new external saves are stored as archives.
+    pub compress_saves: bool,
     pub slots: Vec<Save>,
     pub saves: Vec<Save>,
-    pub backups: Vec<Save>,
+    /// Newest first.
+    pub backups: Vec<Backup>,
+    /// Operations that can be undone, oldest first.
+    pub journal: Vec<JournalEntry>,
+    /// Why the journal couldn't be read, in which case nothing can be
+    /// undone until the next operation starts a new one.
+    pub journal_error: Option<String>,
 }
 
 impl Save {
+    /// The folder an external save is in, e.g. `bosses` for
+    /// `bosses/pre-Yingzhao`, or `""` if it isn't in one.
+    pub fn folder(&self) -> &str {
+        self.name.rsplit_once('/').map_or("", |(folder, _)| folder)
+    }
+
+    /// The save's name without its folder.
+    pub fn short_name(&self) -> &str {
+        self.name
+            .rsplit_once('/')
+            .map_or(&self.name, |(_, name)| name)
+    }
+
+    /// What the save would be called if it were moved into `folder`.
+    pub fn name_in(&self, folder: &str) -> String {
+        match folder.trim_matches('/') {
+            "" => self.short_name().to_string(),
+            folder => format!("{}/{}", folder, self.short_name()),
+        }
+    }
+
+    /// Reads and decrypts `meta.txt`, filling in [`Save::info`].
     pub fn with_decrypted_info(self) -> Result<Self> {
         let info = SaveInfo::decrypt_from(&self)?;
         Ok(Save {
             info: Some(info),
+            error: None,
             ..self
         })
     }
+    /// Like [`Save::with_decrypted_info`], but a save that can't be read
+    /// is kept, with the reason in [`Save::error`].
+    pub fn with_info_or_error(self) -> Self {
+        match SaveInfo::decrypt_from(&self) {
+            Ok(info) => Save {
+                info: Some(info),
+                error: None,
+                ..self
+            },
+            Err(e) => Save {
+                info: None,
+                error: Some(format!("{:#}", e)),
+                ..self
+            },
+        }
+    }
+    /// Copies the save's files into `destination`, creating it if needed.
+    ///
+    /// Either side may be a zip archive, in which case the files are packed
+    /// or unpacked as needed. The copy is checked against the original
+    /// afterwards.
     pub fn copy(&self, destination: &Path) -> Result<()> {
-        fs::create_dir_all(destination).with_context(|| {
-            format!("couldn't create destination directory ({:?})", destination)
-        })?;
-        for entry in fs::read_dir(&self.path)? {
-            let file = entry?;
-            let new_path = destination.join(
-                file.path()
-                    .file_name()
-                    .with_context(|| format!("couldn't get filename of entry {:?}", file))?,
-            );
-            fs::copy(file.path(), new_path)?;
+        self.copy_files(destination)?;
+        self.verify_copy(&Save {
+            path: destination.to_owned(),
+            ..self.clone()
+        })
+    }
+    fn copy_files(&self, destination: &Path) -> Result<()> {
+        match (
+            archive::is_archive(&self.path),
+            archive::is_archive(destination),
+        ) {
+            (false, false) => files::copy_dir(&self.path, destination),
+            (true, false) => archive::extract(&self.path, destination),
+            (from_archive, true) => {
+                if let Some(parent) = destination.parent() {
+                    fs::create_dir_all(parent).with_context(|| {
+                        format!("couldn't create destination directory ({:?})", parent)
+                    })?;
+                }
+                match from_archive {
+                    true => files::copy_file(&self.path, destination),
+                    false => archive::create(&self.path, destination),
+                }
+            }
         }
-        Ok(())
     }
+    /// Removes the save's files, leaving the directory in place. An
+    /// archive is removed entirely.
     pub fn delete(&self) -> Result<()> {
-        for entry in fs::read_dir(&self.path)? {
-            let file = entry?;
-            fs::remove_file(file.path())?;
+        if archive::is_archive(&self.path) {
+            return fs::remove_file(&self.path)
+                .with_context(|| format!("couldn't remove archive {:?}", self.path));
         }
-        Ok(())
+        files::clear_dir(&self.path)
+    }
+    /// A hidden path next to the save, for use while replacing it. Keeps
+    /// the extension, so a staged archive is still an archive.
+    fn sibling(&self, purpose: &str) -> Result<PathBuf> {
+        let name = self
+            .path
+            .file_name()
+            .with_context(|| format!("invalid save path {:?}", self.path))?;
+        Ok(self.path.with_file_name(format!(
+            ".nine_saves_{}_{}",
+            purpose,
+            name.to_string_lossy()
+        )))
     }
+    /// Replaces the save's files with those of `source`.
+    ///
+    /// `source` is copied next to the save first, and only swapped into
+    /// place once the copy is complete, so a failed copy leaves the save
+    /// untouched. If the swap itself fails, the original is put back.
+    pub fn replace_with(&self, source: &Save) -> Result<()> {
+        let staging = self.sibling("new")?;
+        let old = self.sibling("old")?;
+        for leftover in [&staging, &old] {
+            files::remove_path(leftover)?;
+        }
+        if let Err(e) = source.copy(&staging) {
+            let _ = files::remove_path(&staging);
+            return Err(e)
+                .with_context(|| format!("failed to copy {} to {:?}", source.name, self.path));
+        }
+        let existed = self.path.exists();
+        if existed {
+            if let Err(e) = fs::rename(&self.path, &old) {
+                let _ = files::remove_path(&staging);
+                return Err(e).with_context(|| format!("couldn't move {:?} aside", self.path));
+            }
+        }
+        if let Err(e) = fs::rename(&staging, &self.path) {
+            let rollback = match existed {
+                true => fs::rename(&old, &self.path),
+                false => Ok(()),
+            };
+            let _ = files::remove_path(&staging);
+            return match rollback {
+                Ok(()) => Err(e).with_context(|| {
+                    format!("couldn't move new files into {:?}, left it unchanged", self.path)
+                }),
+                Err(rollback) => Err(e).with_context(|| {
+                    format!(
+                        "couldn't move new files into {:?}, and couldn't put the original back from {:?} ({})",
+                        self.path, old, rollback
+                    )
+                }),
+            };
+        }
+        files::remove_path(&old)
+    }
+    /// Removes the save's directory, which must already be empty.
     pub fn delete_dir(&self) -> Result<()> {
+        if archive::is_archive(&self.path) && !self.path.exists() {
+            return Ok(());
+        }
         fs::remove_dir(&self.path)
             .with_context(|| format!("couldn't remove save directory {:?}", self.path))
     }
+    /// Whether the save's directory exists and has anything in it.
+    pub fn has_files(&self) -> bool {
+        if archive::is_archive(&self.path) {
+            return self.path.is_file();
+        }
+        fs::read_dir(&self.path).is_ok_and(|mut entries| entries.next().is_some())
+    }
+    /// Creates the save's directory, or for an archive, the directory it
+    /// goes in.
     pub fn create_dir(&self) -> Result<()> {
-        fs::create_dir_all(&self.path)
-            .with_context(|| format!("couldn't create directory {:?}", self.path))
+        let dir = match archive::is_archive(&self.path) {
+            true => match self.path.parent() {
+                Some(parent) => parent,
+                None => return Ok(()),
+            },
+            false => &self.path,
+        };
+        fs::create_dir_all(dir).with_context(|| format!("couldn't create directory {:?}", dir))
+    }
+    /// Reads one of the save's files, from inside the archive if it is one.
+    pub(crate) fn read_file(&self, name: &str) -> Result<Vec<u8>> {
+        match archive::is_archive(&self.path) {
+            true => archive::read_file(&self.path, name),
+            false => fs::read(self.path.join(name))
+                .with_context(|| format!("couldn't read {:?}", self.path.join(name))),
+        }
+    }
+    /// Replaces one of the save's files, inside the archive if it is one.
+    pub(crate) fn write_file(&self, name: &str, data: &[u8]) -> Result<()> {
+        match archive::is_archive(&self.path) {
+            true => archive::write_file(&self.path, name, data),
+            false => fs::write(self.path.join(name), data)
+                .with_context(|| format!("couldn't write {:?}", self.path.join(name))),
+        }
+    }
+}
+
+impl SaveInfo {
+    /// Parses decrypted `meta.txt` contents.
+    pub fn parse(json: &[u8]) -> Result<Self> {
+        let json: Value = sonic_rs::from_slice(json).context("invalid json")?;
+        Self::try_from(json)
+    }
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+    /// Play time in seconds.
+    pub fn playtime(&self) -> f64 {
+        self.playtime
+    }
+    pub fn gold(&self) -> u32 {
+        self.gold
+    }
+    pub fn gamemode(&self) -> u8 {
+        self.gamemode
+    }
+    /// GUID of the scene the save is in.
+    pub fn atsceneguid(&self) -> &str {
+        &self.atsceneguid
+    }
+    /// Any field of `meta.txt`, by the game's name for it.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.json.get(key)
+    }
+    /// Replaces the field `key`, keeping its place among the others, or
+    /// adds it at the end. Fails, changing nothing, if that would leave a
+    /// field Nine Saves reads invalid.
+    pub fn set(&mut self, key: &str, value: impl Into<Value>) -> Result<()> {
+        let mut json = self.json.clone();
+        json.as_object_mut()
+            .expect("checked when parsed")
+            .insert(&key, value);
+        *self = Self::try_from(json)?;
+        Ok(())
+    }
+    /// The whole object, serialized as the game writes it.
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        sonic_rs::to_vec(&self.json).context("couldn't serialize metadata")
+    }
+    /// Play time formatted as e.g. `"3h 25m"`.
+    pub fn formatted_time(&self) -> String {
+        let hours = (self.playtime / 3600.).trunc() as u32;
+        let minutes = ((self.playtime / 60.) % 60.).trunc() as u8;
+        let h_disp = match hours {
+            0 => "".to_string(),
+            _ => format!("{}h ", hours),
+        };
+        let m_disp = format!("{}m", minutes);
+        format!("{}{}", h_disp, m_disp)
     }
 }
 
 impl SavesData {
+    /// Reloads the slots, external saves and backups from disk.
     pub fn refresh(&mut self) -> Result<()> {
-        let re = Regex::new("saveslot([0-3])(_BeforeNoReturnPoint)?$")?;
         self.slots = fs::read_dir(&self.game_slots_dir)
             .context("couldn't read game's slot directory")?
-            .filter_map(|x| match x {
-                Ok(x) => Some(x),
-                Err(_) => None,
-            })
-            .filter_map(|p| {
-                let name = p.file_name().into_string();
-                match name {
-                    Ok(n) => Some((n, p.path().to_owned())),
-                    Err(_) => None,
-                }
-            })
-            .filter_map(|p| {
-                if let Some(caps) = re.captures(&p.0) {
-                    match caps.get(1) {
-                        Some(num) => {
-                            let num: u8 = num.as_str().parse().unwrap();
-                            match caps.get(2) {
-                                Some(_) => Some(Save {
-                                    name: format!("Slot {} (Before NRP)", num + 1),
-                                    path: p.1,
-                                    nrp_backup: true,
-                                    exists: true,
-                                    info: None,
-                                }),
-                                None => Some(Save {
-                                    name: format!("Slot {}", num + 1),
-                                    path: p.1,
-                                    nrp_backup: false,
-                                    exists: true,
-                                    info: None,
-                                }),
-                            }
-                        }
-                        None => None,
-                    }
-                } else {
-                    None
-                }
-            })
-            .filter_map(|s| s.with_decrypted_info().ok())
+            .filter_map(|x| x.ok())
+            .filter_map(|e| game_slot(&e.path()))
+            // Deleting a slot leaves its directory behind, empty.
+            .filter(|s| s.has_files())
+            .map(Save::with_info_or_error)
             .collect();
         for num in 0..4 {
             let name = format!("Slot {}", num + 1);
@@ -208,6 +495,8 @@ impl SavesData {
                     nrp_backup: false,
                     exists: false,
                     info: None,
+                    error: None,
+                    sidecar: Sidecar::default(),
                 };
                 self.slots.push(slot)
             }
@@ -217,42 +506,510 @@ impl SavesData {
         self.saves =
             saves_from_dir(&self.external_saves_dir).context("failed to load external saves")?;
         fs::create_dir_all(&self.backups_dir).context("couldn't create backups directory")?;
-        self.backups = saves_from_dir(&self.backups_dir).context("failed to load backups")?;
+        self.backups = backups_from_dir(&self.backups_dir).context("failed to load backups")?;
+        (self.journal, self.journal_error) = match self.load_journal() {
+            Ok(journal) => (journal, None),
+            Err(e) => (vec![], Some(format!("{:#}", e))),
+        };
 
         self.slots.sort_by(|a, b| a.name.cmp(&b.name));
-        self.saves.sort_by(|a, b| a.name.cmp(&b.name));
+        // Sorting by folder first keeps each folder's saves together.
+        self.saves
+            .sort_by(|a, b| (a.folder(), a.short_name()).cmp(&(b.folder(), b.short_name())));
 
         Ok(())
     }
 
-    pub fn backup_and_overwrite(&self, source: &Save, destination: &Save) -> Result<()> {
-        self.backup_and_delete(destination)?;
-        source
-            .copy(&destination.path)
-            .with_context(|| format!("failed to copy {} to {:?}", source.name, destination.path))?;
-        Ok(())
+    /// Backs up `destination`, then replaces its files with those of
+    /// `source`. If that fails, `destination` is left as it was.
+    pub fn backup_and_overwrite(&self, source: &Save, destination: &Save) -> Result<Backup> {
+        let backup = self.backup(destination, Operation::Overwrite)?;
+        destination.replace_with(source)?;
+        Ok(backup)
     }
 
-    pub fn backup_and_delete(&self, save: &Save) -> Result<()> {
-        let backup_dst = &self
-            .backups_dir
-            .join(format!("{}_{}", self.backups.len(), &save.name));
-        save.copy(backup_dst)
-            .with_context(|| format!("failed to back up save {}", save.name))?;
+    /// Copies `save` into the backups directory, then deletes its files.
+    pub fn backup_and_delete(&self, save: &Save) -> Result<Backup> {
+        let backup = self.backup(save, Operation::Delete)?;
         save.delete()
             .with_context(|| format!("failed to delete save {}", save.name))?;
-        Ok(())
+        Ok(backup)
     }
 
+    /// Finds game slot `num` (1 to 4), not counting "Before NRP" copies.
+    pub fn slot(&self, num: usize) -> Option<&Save> {
+        let name = format!("Slot {}", num);
+        self.slots.iter().find(|s| s.name == name)
+    }
+
+    /// Finds the external save called `name`.
+    pub fn save(&self, name: &str) -> Option<&Save> {
+        self.saves.iter().find(|s| s.name == name)
+    }
+
+    /// Whether `name` can be used for a new external save. `/` separates
+    /// folders, as in `bosses/pre-Yingzhao`. No part of the name can be
+    /// empty or start with a dot, which would hide the save, a save can't
+    /// go inside another one, and it can't take the name of a folder.
+    pub fn name_available(&self, name: &str) -> bool {
+        name.split('/')
+            .all(|part| !part.is_empty() && !part.starts_with('.') && !part.contains('\\'))
+            && !self.saves.iter().any(|s| {
+                s.name == name
+                    || name.starts_with(&format!("{}/", s.name))
+                    || s.name.starts_with(&format!("{}/", name))
+            })
+    }
+
+    /// Whether `path` is inside the external saves directory.
+    pub(crate) fn is_external(&self, path: &Path) -> bool {
+        path.starts_with(&self.external_saves_dir) && path != self.external_saves_dir
+    }
+
+    /// Removes the folders around the external save that was at `path` that
+    /// are now empty, since they'd otherwise be listed as saves.
+    fn remove_empty_folders(&self, path: &Path) {
+        for parent in path.ancestors().skip(1) {
+            // Fails at the first folder that still has something in it.
+            if !self.is_external(parent) || fs::remove_dir(parent).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Where a new external save called `name` goes, checking that the name
+    /// is available.
+    fn new_save_path(&self, name: &str, archive: bool) -> Result<PathBuf> {
+        let path = match archive {
+            true => self
+                .external_saves_dir
+                .join(format!("{}.{}", name, archive::EXTENSION)),
+            false => self.external_saves_dir.join(name),
+        };
+        if !self.name_available(name) || path.exists() {
+            return Err(anyhow!("invalid or already used save name {:?}", name));
+        }
+        Ok(path)
+    }
+
+    /// Copies `slot` to a new external save called `name`.
+    pub fn save_slot_to_new(&self, slot: &Save, name: &str) -> Result<()> {
+        let destination = self.new_save_path(name, self.compress_saves)?;
+        if !slot.exists {
+            return Err(anyhow!("{} is empty", slot.name));
+        }
+        slot.copy(&destination)
+            .with_context(|| format!("failed to copy {} to new save {}", slot.name, name))?;
+        self.record_checksums(&Save {
+            name: name.to_string(),
+            path: destination,
+            ..slot.clone()
+        })
+    }
+
+    /// Writes `source` into `slot`, backing up the slot first if it's in
+    /// use. Can be undone.
+    pub fn write_to_slot(&self, source: &Save, slot: &Save) -> Result<()> {
+        let backup = if slot.exists {
+            Some(self.backup_and_overwrite(source, slot)?)
+        } else {
+            slot.create_dir()?;
+            source
+                .copy(&slot.path)
+                .with_context(|| format!("failed to copy {} to {:?}", source.name, slot.path))?;
+            None
+        };
+        self.record(
+            format!("wrote {} to {}", source.name, slot.name),
+            slot,
+            backup.as_ref(),
+        )
+    }
+
+    /// Overwrites the external save `save` with `slot`, backing it up
+    /// first. Can be undone.
+    pub fn write_slot_to_external(&self, slot: &Save, save: &Save) -> Result<Backup> {
+        let backup = self.backup_and_overwrite(slot, save)?;
+        self.record_checksums(save)?;
+        self.record(
+            format!("wrote {} to {}", slot.name, save.name),
+            save,
+            Some(&backup),
+        )?;
+        Ok(backup)
+    }
+
+    /// Backs up and deletes a game slot. Can be undone.
+    pub fn delete_slot(&self, slot: &Save) -> Result<Backup> {
+        let backup = self.backup_and_delete(slot)?;
+        self.record(format!("deleted {}", slot.name), slot, Some(&backup))?;
+        Ok(backup)
+    }
+
+    /// Renames an external save, keeping its sidecar with it. Naming it
+    /// into another folder moves it there, and a folder left empty is
+    /// removed.
+    pub fn rename_external(&self, save: &Save, name: &str) -> Result<()> {
+        let renamed = Save {
+            name: name.to_string(),
+            path: self.new_save_path(name, archive::is_archive(&save.path))?,
+            ..save.clone()
+        };
+        if let Some(parent) = renamed.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("couldn't create folder {:?}", parent))?;
+        }
+        fs::rename(&save.path, &renamed.path)
+            .with_context(|| format!("couldn't rename {} to {}", save.name, name))?;
+        save.move_sidecar_to(&renamed)?;
+        self.remove_empty_folders(&save.path);
+        self.redirect_journal(&save.path, &renamed.path)?;
+        self.redirect_backups(&save.path, &renamed)
+    }
+
+    /// Moves an external save into `folder`, or out of any folder if it's
+    /// empty.
+    pub fn move_external(&self, save: &Save, folder: &str) -> Result<()> {
+        self.rename_external(save, &save.name_in(folder))
+    }
+
+    /// Copies an external save to a new one called `name`, along with its
+    /// sidecar.
+    pub fn duplicate_external(&self, save: &Save, name: &str) -> Result<()> {
+        let copy = Save {
+            name: name.to_string(),
+            path: self.new_save_path(name, archive::is_archive(&save.path))?,
+            ..save.clone()
+        };
+        save.copy(&copy.path)
+            .with_context(|| format!("failed to duplicate {} as {}", save.name, name))?;
+        save.copy_sidecar_to(&copy)
+    }
+
+    /// Adds the save in the directory or zip archive at `source` to the
+    /// external saves as `name`. It's checked to be a Nine Sols save by
+    /// decrypting its `meta.txt` first.
+    ///
+    /// Shared saves are often packed inside a folder of their own, so if
+    /// everything in `source` is in one folder, that folder is imported.
+    pub fn import(&self, source: &Path, name: &str) -> Result<()> {
+        let is_archive = archive::is_archive(source) && source.is_file();
+        if !source.is_dir() && !is_archive {
+            return Err(anyhow!(
+                "{:?} isn't a save directory or a zip archive",
+                source
+            ));
+        }
+        if !is_archive {
+            let dir = only_folder(source).unwrap_or_else(|| source.to_owned());
+            return self.import_files(&dir, source, name);
+        }
+        let Some(folder) = archive::top_folder(source)? else {
+            return self.import_files(source, source, name);
+        };
+        let staging = self.external_saves_dir.join(".nine_saves_import");
+        files::remove_path(&staging)?;
+        let result = archive::extract(source, &staging)
+            .and_then(|_| self.import_files(&staging.join(folder), source, name));
+        let _ = files::remove_path(&staging);
+        result
+    }
+
+    /// Imports the save at `path`, which is `source` or inside it.
+    fn import_files(&self, path: &Path, source: &Path, name: &str) -> Result<()> {
+        let imported = Save {
+            name: name.to_string(),
+            path: path.to_owned(),
+            nrp_backup: false,
+            exists: true,
+            info: None,
+            error: None,
+            sidecar: Sidecar::default(),
+        }
+        .with_decrypted_info()
+        .with_context(|| {
+            format!(
+                "{:?} isn't a readable Nine Sols save, its meta.txt must be at the top or in its only folder",
+                source
+            )
+        })?;
+        let destination = self.new_save_path(name, self.compress_saves)?;
+        imported
+            .copy(&destination)
+            .with_context(|| format!("failed to import {:?} as {}", source, name))?;
+        self.record_checksums(&Save {
+            path: destination,
+            ..imported
+        })
+    }
+
+    /// Backs up and removes an external save entirely. Can be undone.
+    pub fn delete_external(&self, save: &Save) -> Result<()> {
+        let backup = self.backup_and_delete(save)?;
+        save.delete_dir()?;
+        save.delete_sidecar()?;
+        self.remove_empty_folders(&save.path);
+        self.record(format!("deleted {}", save.name), save, Some(&backup))
+    }
+
+    /// Writes a backup back to where it was taken from, backing up whatever
+    /// is there now. Returns that backup, if one was needed.
+    pub fn restore(&self, backup: &Backup) -> Result<Option<Backup>> {
+        if let Some(error) = &backup.error {
+            return Err(anyhow!("backup {} can't be restored: {}", backup.id, error));
+        }
+        let manifest = backup
+            .manifest
+            .as_ref()
+            .with_context(|| format!("backup {} has no record of where it came from", backup.id))?;
+        let source_path = &manifest.source_path;
+        if source_path.parent() != Some(&self.game_slots_dir) && !self.is_external(source_path) {
+            return Err(anyhow!(
+                "backup {} came from {:?}, which Nine Saves no longer manages",
+                backup.id,
+                source_path
+            ));
+        }
+        let destination = self
+            .slots
+            .iter()
+            .chain(&self.saves)
+            .find(|s| &s.path == source_path)
+            .cloned()
+            .unwrap_or_else(|| Save {
+                name: manifest.source_name.clone(),
+                path: source_path.clone(),
+                nrp_backup: false,
+                exists: source_path.exists(),
+                info: None,
+                error: None,
+                sidecar: Sidecar::default(),
+            });
+        // Checking the disk rather than `exists` also catches slots whose
+        // directory is there but empty.
+        let replaced = self.with_backup_files(backup, |files| {
+            if destination.has_files() {
+                self.backup_and_overwrite(files, &destination).map(Some)
+            } else {
+                destination.create_dir()?;
+                files
+                    .copy(&destination.path)
+                    .with_context(|| format!("failed to restore backup {}", backup.id))?;
+                Ok(None)
+            }
+        })?;
+        if let Some(sidecar) = &manifest.sidecar {
+            destination.set_notes(&sidecar.notes, sidecar.tags.clone())?;
+        }
+        self.record_checksums(&destination)?;
+        Ok(replaced)
+    }
+
+    /// Locates the game's save directory and Nine Saves' data directory,
+    /// using the user's [`Config`]. Call [`SavesData::refresh`] to load the
+    /// saves themselves.
     pub fn new() -> Result<Self> {
+        Self::with_config(&Config::load()?)
+    }
+
+    /// Like [`SavesData::new`], but with the directories set in `config`
+    /// instead of the user's config file.
+    pub fn with_config(config: &Config) -> Result<Self> {
         let base_dirs = BaseDirs::new().context("couldn't get base directories for OS")?;
+        let game_slots_dir = match &config.game_slots_dir {
+            Some(dir) => dir.clone(),
+            None => save_directory()?,
+        };
+        let external_saves_dir = match &config.external_saves_dir {
+            Some(dir) => dir.clone(),
+            None => data_dir(&base_dirs)?.join("saves"),
+        };
+        let backups_dir = match &config.backups_dir {
+            Some(dir) => dir.clone(),
+            None => data_dir(&base_dirs)?.join("backups"),
+        };
         Ok(Self {
-            game_slots_dir: save_directory(&base_dirs)?,
-            external_saves_dir: data_dir(&base_dirs)?.join("saves"),
-            backups_dir: data_dir(&base_dirs)?.join("backups"),
+            game_slots_dir,
+            external_saves_dir,
+            backups_dir,
+            retention: config.retention.clone(),
+            backup_storage: config.backup_storage,
+            compress_saves: config.compress_saves,
             slots: vec![],
             saves: vec![],
             backups: vec![],
+            journal: vec![],
+            journal_error: None,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{save, saves_data, write_save, TempDir};
+
+    #[test]
+    fn replace() {
+        let root = TempDir::new("save");
+        let (source, destination) = (save(root.join("source")), save(root.join("saveslot0")));
+        fs::create_dir_all(&source.path).unwrap();
+        fs::create_dir_all(&destination.path).unwrap();
+        fs::write(source.path.join("meta.txt"), b"new").unwrap();
+        fs::create_dir_all(source.path.join("mods")).unwrap();
+        fs::write(source.path.join("mods").join("data.txt"), b"nested").unwrap();
+        fs::write(destination.path.join("meta.txt"), b"old").unwrap();
+        fs::write(destination.path.join("stale.txt"), b"old").unwrap();
+
+        let missing = save(root.join("missing"));
+        assert!(destination.replace_with(&missing).is_err());
+        assert_eq!(fs::read(destination.path.join("meta.txt")).unwrap(), b"old");
+
+        destination.replace_with(&source).unwrap();
+        assert_eq!(fs::read(destination.path.join("meta.txt")).unwrap(), b"new");
+        assert!(!destination.path.join("stale.txt").exists());
+        assert_eq!(fs::read_dir(&*root).unwrap().count(), 2);
+        assert!(destination.path.join("mods").join("data.txt").exists());
+
+        destination.delete().unwrap();
+        destination.delete_dir().unwrap();
+    }
+
+    #[test]
+    fn unreadable_saves_are_listed() {
+        let root = TempDir::new("unreadable");
+        fs::create_dir_all(root.join("bad")).unwrap();
+        fs::write(root.join("bad").join("meta.txt"), b"garbage").unwrap();
+
+        let saves = saves_from_dir(&root).unwrap();
+        assert_eq!(saves.len(), 1);
+        assert!(saves[0].info.is_none());
+        assert!(saves[0].error.is_some());
+    }
+
+    #[test]
+    fn folders() {
+        let root = TempDir::new("folders");
+        let mut data = saves_data(&root);
+        let saves = &data.external_saves_dir;
+        write_save(&saves.join("bosses").join("a"));
+        data.saves = saves_from_dir(saves).unwrap();
+        let a = data.save("bosses/a").unwrap().clone();
+        assert_eq!((a.folder(), a.short_name()), ("bosses", "a"));
+        assert!(!data.name_available("bosses/a"));
+        assert!(!data.name_available("bosses/a/b"));
+        assert!(!data.name_available("bosses/.b"));
+        assert!(!data.name_available("bosses"));
+        assert!(data.name_available("routes/b"));
+
+        data.move_external(&a, "routes/ji").unwrap();
+        assert!(!data.external_saves_dir.join("bosses").exists());
+        data.saves = saves_from_dir(&data.external_saves_dir).unwrap();
+        data.move_external(data.save("routes/ji/a").unwrap(), "")
+            .unwrap();
+        assert!(data.external_saves_dir.join("a").join("meta.txt").exists());
+        assert!(!data.external_saves_dir.join("routes").exists());
+
+        // Directories without saves in them are saves, however broken.
+        fs::create_dir_all(saves.join("empty")).unwrap();
+        fs::create_dir_all(saves.join("nested").join("data")).unwrap();
+        fs::write(saves.join("nested").join("data").join("x"), b"").unwrap();
+        write_save(&saves.join("tagged").join("inner"));
+        fs::remove_file(saves.join("tagged").join("inner").join("meta.txt")).unwrap();
+        fs::write(saves.join("tagged.nine_saves.json"), b"{}").unwrap();
+        let names: Vec<String> = saves_from_dir(saves)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names.len(), 4);
+        for name in ["a", "empty", "nested", "tagged"] {
+            assert!(names.contains(&name.to_string()), "{} not listed", name);
+        }
+
+        write_save(&saves.join("old").join("b"));
+        data.saves = saves_from_dir(saves).unwrap();
+        data.delete_external(data.save("old/b").unwrap()).unwrap();
+        assert!(!saves.join("old").exists());
+    }
+
+    #[test]
+    fn import() {
+        let root = TempDir::new("import");
+        let (good, bad) = (root.join("good"), root.join("bad"));
+        write_save(&good);
+        fs::create_dir_all(&bad).unwrap();
+        fs::write(bad.join("meta.txt"), b"garbage").unwrap();
+        let data = saves_data(&root);
+
+        assert!(data.import(&bad, "bad").is_err());
+        assert!(!root.join("saves").join("bad").exists());
+        data.import(&good, "friends/good").unwrap();
+        let saves = saves_from_dir(&root.join("saves")).unwrap();
+        assert_eq!(saves[0].name, "friends/good");
+        assert!(saves[0].info.is_some());
+        assert!(saves[0].sidecar.checksums.is_some());
+
+        // Saves zipped or copied along with their directory.
+        let wrapped = root.join("wrapped");
+        write_save(&wrapped.join("NineSols"));
+        fs::write(wrapped.join(".DS_Store"), b"").unwrap();
+        fs::create_dir_all(wrapped.join("__MACOSX").join("NineSols")).unwrap();
+        fs::write(
+            wrapped.join("__MACOSX").join("NineSols").join("._meta.txt"),
+            b"",
+        )
+        .unwrap();
+        let zipped = root.join("wrapped.zip");
+        archive::create(&wrapped, &zipped).unwrap();
+        data.import(&wrapped, "copied").unwrap();
+        data.import(&zipped, "zipped").unwrap();
+        let saves = saves_from_dir(&root.join("saves")).unwrap();
+        assert_eq!(saves.len(), 3);
+        assert!(saves.iter().all(|s| s.info.is_some()));
+        assert!(!root.join("saves").join(".nine_saves_import").exists());
+    }
+
+    #[test]
+    fn rename_and_duplicate() {
+        let root = TempDir::new("rename");
+        write_save(&root.join("saves").join("a"));
+        write_save(&root.join("saves").join("b"));
+        write_save(&root.join("slots").join("saveslot0"));
+        fs::write(
+            root.join("slots").join("saveslot0").join("flags.txt"),
+            b"slot",
+        )
+        .unwrap();
+        let mut data = saves_data(&root);
+        data.refresh().unwrap();
+        let a = data.save("a").unwrap();
+        a.set_notes("before the boss", vec!["boss".to_string()])
+            .unwrap();
+        data.write_slot_to_external(data.slot(1).unwrap(), a)
+            .unwrap();
+        data.refresh().unwrap();
+
+        let a = data.save("a").unwrap();
+        for name in ["b", "", ".hidden", "b/nested"] {
+            assert!(data.rename_external(a, name).is_err());
+            assert!(data.duplicate_external(a, name).is_err());
+        }
+        data.duplicate_external(a, "copy").unwrap();
+        data.rename_external(a, "bosses/a").unwrap();
+        data.refresh().unwrap();
+        assert!(data.save("a").is_none());
+        for name in ["copy", "bosses/a"] {
+            assert_eq!(data.save(name).unwrap().sidecar.notes, "before the boss");
+            assert_eq!(data.save(name).unwrap().sidecar.tags, ["boss"]);
+        }
+
+        // Undoing the overwrite restores the save under its new name.
+        let renamed = root.join("saves").join("bosses").join("a");
+        assert_eq!(data.load_journal().unwrap()[0].path, renamed);
+        data.undo().unwrap().unwrap();
+        assert!(!root.join("saves").join("a").exists());
+        assert!(!renamed.join("flags.txt").exists());
+        assert!(renamed.join("meta.txt").exists());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::archive;
+use crate::files;
+use crate::save::{Save, SaveInfo, SavesData};
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::time::SystemTime;
+
+/// The order external saves are listed in. Saves stay grouped by folder
+/// whichever is used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Alphabetically.
+    #[default]
+    Name,
+    /// Highest level first.
+    Level,
+    /// Longest played first.
+    Playtime,
+    /// Most gold first.
+    Gold,
+    /// Alphabetically by location name.
+    Location,
+    /// Most recently written first.
+    Modified,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 6] = [
+        SortOrder::Name,
+        SortOrder::Level,
+        SortOrder::Playtime,
+        SortOrder::Gold,
+        SortOrder::Location,
+        SortOrder::Modified,
+    ];
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SortOrder::Name => "Name",
+            SortOrder::Level => "Level",
+            SortOrder::Playtime => "Playtime",
+            SortOrder::Gold => "Gold",
+            SortOrder::Location => "Location",
+            SortOrder::Modified => "Last modified",
+        })
+    }
+}
+
+/// Which external saves to list, and in what order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SaveQuery {
+    /// Text to look for in a save's name, location, notes and tags,
+    /// ignoring case. Empty matches everything.
+    pub search: String,
+    /// Only list saves with this tag.
+    pub tag: Option<String>,
+    /// Only list saves in this game mode.
+    pub game_mode: Option<u8>,
+    pub sort: SortOrder,
+}
+
+/// Compares two values that may be unknown, putting unknown ones last.
+fn known_first<T>(a: Option<T>, b: Option<T>, compare: impl FnOnce(T, T) -> Ordering) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => compare(a, b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+impl SaveQuery {
+    /// Whether `save` passes the search and filters.
+    pub fn matches(&self, save: &Save) -> bool {
+        let info = save.info.as_ref();
+        if self.game_mode.is_some() && info.map(SaveInfo::gamemode) != self.game_mode {
+            return false;
+        }
+        if let Some(tag) = &self.tag {
+            if !save.sidecar.tags.contains(tag) {
+                return false;
+            }
+        }
+        let search = self.search.trim().to_lowercase();
+        if search.is_empty() {
+            return true;
+        }
+        [
+            Some(save.name.as_str()),
+            info.and_then(SaveInfo::location),
+            Some(save.sidecar.notes.as_str()),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(save.sidecar.tags.iter().map(String::as_str))
+        .any(|text| text.to_lowercase().contains(&search))
+    }
+
+    /// Positions in `saves` of the saves that match, in the query's order.
+    pub fn apply(&self, saves: &[Save]) -> Vec<usize> {
+        let modified: Vec<Option<SystemTime>> = match self.sort {
+            SortOrder::Modified => saves.iter().map(Save::modified).collect(),
+            _ => vec![],
+        };
+        let mut shown: Vec<usize> = (0..saves.len())
+            .filter(|&i| self.matches(&saves[i]))
+            .collect();
+        shown.sort_by(|&i, &j| {
+            let (a, b) = (&saves[i], &saves[j]);
+            let (x, y) = (a.info.as_ref(), b.info.as_ref());
+            let order = match self.sort {
+                SortOrder::Name => Ordering::Equal,
+                SortOrder::Level => known_first(x, y, |x, y| y.level().cmp(&x.level())),
+                SortOrder::Playtime => {
+                    known_first(x, y, |x, y| y.playtime().total_cmp(&x.playtime()))
+                }
+                SortOrder::Gold => known_first(x, y, |x, y| y.gold().cmp(&x.gold())),
+                SortOrder::Location => known_first(
+                    x.and_then(SaveInfo::location),
+                    y.and_then(SaveInfo::location),
+                    |x, y| x.cmp(y),
+                ),
+                SortOrder::Modified => known_first(modified[i], modified[j], |x, y| y.cmp(&x)),
+            };
+            a.folder()
+                .cmp(b.folder())
+                .then(order)
+                .then_with(|| a.short_name().cmp(b.short_name()))
+        });
+        shown
+    }
+}
+
+impl Save {
+    /// When the save's files were last written, if it can be found out.
+    pub fn modified(&self) -> Option<SystemTime> {
+        match archive::is_archive(&self.path) {
+            true => files::modified(&self.path).ok(),
+            false => files::walk(&self.path)
+                .ok()?
+                .iter()
+                .filter_map(|(_, path)| files::modified(path).ok())
+                .max(),
+        }
+    }
+}
+
+impl SavesData {
+    /// Every tag used by an external save, sorted.
+    pub fn tags(&self) -> Vec<&str> {
+        let mut tags: Vec<&str> = self
+            .saves
+            .iter()
+            .flat_map(|s| s.sidecar.tags.iter().map(String::as_str))
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
+
+    /// Every game mode an external save is in, sorted.
+    pub fn game_modes(&self) -> Vec<u8> {
+        let mut modes: Vec<u8> = self
+            .saves
+            .iter()
+            .filter_map(|s| s.info.as_ref().map(SaveInfo::gamemode))
+            .collect();
+        modes.sort_unstable();
+        modes.dedup();
+        modes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sidecar::Sidecar;
+    use crate::test_util::META;
+    use std::path::PathBuf;
+
+    fn save(name: &str, level: Option<u8>, tags: &[&str]) -> Save {
+        Save {
+            name: name.to_string(),
+            path: PathBuf::from(name),
+            nrp_backup: false,
+            exists: true,
+            info: level.map(|level| {
+                let mut info = SaveInfo::parse(META).unwrap();
+                info.set("level", level).unwrap();
+                info
+            }),
+            error: None,
+            sidecar: Sidecar {
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn sort_and_filter() {
+        let saves = vec![
+            save("a", Some(3), &["boss"]),
+            save("b", None, &[]),
+            save("c", Some(7), &["boss"]),
+            save("run/d", Some(9), &[]),
+        ];
+        let mut query = SaveQuery {
+            sort: SortOrder::Level,
+            ..Default::default()
+        };
+        assert_eq!(query.apply(&saves), vec![2, 0, 1, 3]);
+
+        query.tag = Some("boss".to_string());
+        assert_eq!(query.apply(&saves), vec![2, 0]);
+        query.tag = None;
+        query.search = "RUN/".to_string();
+        assert_eq!(query.apply(&saves), vec![3]);
+        query.search.clear();
+        query.game_mode = Some(0);
+        assert_eq!(query.apply(&saves), vec![2, 0, 3]);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::archive;
+use crate::save::Save;
+use crate::store::FileHashes;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Ending of the file stored next to an external save.
+pub(crate) const SIDECAR_SUFFIX: &str = ".nine_saves.json";
+
+/// What Nine Saves records about an external save, kept in a file next to
+/// it so the save's own files stay exactly as the game wrote them.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Sidecar {
+    /// Free-form notes about the save.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// The checksum of each of the save's files, when it was last written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksums: Option<FileHashes>,
+}
+
+/// Splits comma-separated tags, dropping empty and repeated ones.
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    let mut parsed: Vec<String> = vec![];
+    for tag in tags.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        if !parsed.iter().any(|t| t == tag) {
+            parsed.push(tag.to_string());
+        }
+    }
+    parsed
+}
+
+impl Save {
+    /// Where the save's [`Sidecar`] is stored. A save and its zipped form
+    /// share one.
+    pub fn sidecar_path(&self) -> PathBuf {
+        let name = match archive::is_archive(&self.path) {
+            true => self.path.file_stem(),
+            false => self.path.file_name(),
+        };
+        let name = name.unwrap_or_default().to_string_lossy();
+        self.path
+            .with_file_name(format!("{}{}", name, SIDECAR_SUFFIX))
+    }
+
+    /// Fills in [`Save::sidecar`] from disk. An invalid sidecar is
+    /// reported in [`Save::error`].
+    pub fn with_sidecar(self) -> Self {
+        match self.sidecar() {
+            Ok(sidecar) => Save { sidecar, ..self },
+            Err(e) => Save {
+                error: self.error.or(Some(format!("{:#}", e))),
+                ..self
+            },
+        }
+    }
+
+    /// Replaces the save's notes and tags, keeping the rest of its sidecar.
+    pub fn set_notes(&self, notes: &str, tags: Vec<String>) -> Result<()> {
+        let mut sidecar = self.sidecar()?;
+        sidecar.notes = notes.to_string();
+        sidecar.tags = tags;
+        self.write_sidecar(&sidecar)
+    }
+
+    /// Reads the save's sidecar, or an empty one if it has none.
+    pub fn sidecar(&self) -> Result<Sidecar> {
+        let path = self.sidecar_path();
+        if !path.exists() {
+            return Ok(Sidecar::default());
+        }
+        let data = fs::read(&path).with_context(|| format!("couldn't read {:?}", path))?;
+        sonic_rs::from_slice(&data).with_context(|| format!("invalid sidecar file {:?}", path))
+    }
+
+    pub fn write_sidecar(&self, sidecar: &Sidecar) -> Result<()> {
+        let path = self.sidecar_path();
+        let data = sonic_rs::to_string_pretty(sidecar).context("couldn't serialize sidecar")?;
+        fs::write(&path, data).with_context(|| format!("couldn't write {:?}", path))
+    }
+
+    /// Removes the save's sidecar, if it has one.
+    pub fn delete_sidecar(&self) -> Result<()> {
+        let path = self.sidecar_path();
+        match path.exists() {
+            true => fs::remove_file(&path).with_context(|| format!("couldn't remove {:?}", path)),
+            false => Ok(()),
+        }
+    }
+
+    /// Gives `other` a copy of this save's sidecar, if it has one.
+    pub(crate) fn copy_sidecar_to(&self, other: &Save) -> Result<()> {
+        let (from, to) = (self.sidecar_path(), other.sidecar_path());
+        if from.exists() {
+            fs::copy(&from, &to)
+                .with_context(|| format!("couldn't copy {:?} to {:?}", from, to))?;
+        }
+        Ok(())
+    }
+
+    /// Moves this save's sidecar, if it has one, to go with `other`.
+    pub(crate) fn move_sidecar_to(&self, other: &Save) -> Result<()> {
+        let (from, to) = (self.sidecar_path(), other.sidecar_path());
+        if from.exists() {
+            fs::rename(&from, &to)
+                .with_context(|| format!("couldn't move {:?} to {:?}", from, to))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags() {
+        assert_eq!(
+            parse_tags(" boss, practice,,boss "),
+            vec!["boss".to_string(), "practice".to_string()]
+        );
+        assert!(parse_tags(" , ").is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::backup::Backup;
+use crate::files;
+
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// A save's files, as paths relative to the save directory mapped to the
+/// hash of their contents.
+pub type FileHashes = BTreeMap<String, String>;
+
+/// SHA-256 of `data`, as lowercase hex.
+pub fn hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// How long an object is kept after it was last stored, even if no backup
+/// refers to it. A backup's files are stored before its manifest is
+/// written, so garbage collection running meanwhile, for example in
+/// another Nine Saves process, mustn't remove them.
+pub const GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Content-addressed file storage for deduplicated backups.
+///
+/// Each distinct file is stored once, named by its hash. Backups refer to
+/// files by hash, so a backup only costs as much space as the files that
+/// changed since the last one.
+pub struct Store {
+    dir: PathBuf,
+    grace_period: Duration,
+}
+
+impl Store {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            grace_period: GRACE_PERIOD,
+        }
+    }
+
+    pub fn with_grace_period(self, grace_period: Duration) -> Self {
+        Self {
+            grace_period,
+            ..self
+        }
+    }
+
+    /// Where the object with `hash` is stored. Hashes come from manifests on
+    /// disk, so anything that isn't a SHA-256 hash is rejected.
+    fn object_path(&self, hash: &str) -> Result<PathBuf> {
+        if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(anyhow!("invalid object hash {:?}", hash));
+        }
+        Ok(self.dir.join(&hash[..2]).join(hash))
+    }
+
+    fn add_file(&self, data: &[u8]) -> Result<String> {
+        let hash = hash(data);
+        let path = self.object_path(&hash)?;
+        // An object that's already stored is reused, and touched so that
+        // it gets the same grace period as a new one.
+        if files::set_modified(&path, SystemTime::now()).is_ok() {
+            return Ok(hash);
+        }
+        let parent = path.parent().expect("objects are in a subdirectory");
+        fs::create_dir_all(parent).context("couldn't create store directory")?;
+        // Write under a temporary name first so an interrupted write
+        // never leaves a truncated object behind under the real one.
+        let tmp = parent.join(format!("{}.tmp", hash));
+        fs::write(&tmp, data).with_context(|| format!("couldn't write object {}", hash))?;
+        fs::rename(&tmp, &path).with_context(|| format!("couldn't store object {}", hash))?;
+        Ok(hash)
+    }
+
+    /// Stores every file under `dir`, returning their hashes.
+    pub fn add_dir(&self, dir: &Path) -> Result<FileHashes> {
+        let mut files = FileHashes::new();
+        for (relative, path) in files::walk(dir)? {
+            let data = fs::read(&path).with_context(|| format!("couldn't read {:?}", path))?;
+            files.insert(relative, self.add_file(&data)?);
+        }
+        Ok(files)
+    }
+
+    /// Writes the files listed in `files` into `destination`.
+    pub fn checkout(&self, files: &FileHashes, destination: &Path) -> Result<()> {
+        for (relative, hash) in files {
+            // Like archive entries, manifest paths must stay inside the save.
+            let enclosed = Path::new(relative)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+            if !enclosed {
+                return Err(anyhow!("unsafe path {:?} in backup manifest", relative));
+            }
+            let target = destination.join(relative);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("couldn't create directory {:?}", parent))?;
+            }
+            fs::copy(self.object_path(hash)?, &target)
+                .with_context(|| format!("couldn't check out {} (object {})", relative, hash))?;
+            let data = fs::read(&target).with_context(|| format!("couldn't read {:?}", target))?;
+            if self::hash(&data) != *hash {
+                return Err(anyhow!(
+                    "checked out {} doesn't match its checksum, the store may be damaged",
+                    relative
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Total size in bytes of the files listed in `files`. Files shared
+    /// with other backups are counted in full.
+    pub fn size(&self, files: &FileHashes) -> u64 {
+        files
+            .values()
+            .filter_map(|hash| fs::metadata(self.object_path(hash).ok()?).ok())
+            .map(|m| m.len())
+            .sum()
+    }
+
+    /// Removes objects that none of `backups` refer to, returning their
+    /// paths. With `dry_run`, nothing is removed. Objects stored within
+    /// the grace period are kept regardless, and nothing is removed while
+    /// a backup's manifest can't be read, since it may refer to anything.
+    pub fn collect_garbage(&self, backups: &[Backup], dry_run: bool) -> Result<Vec<PathBuf>> {
+        if let Some(backup) = backups.iter().find(|b| b.error.is_some()) {
+            return Err(anyhow!(
+                "backup {} can't be read, so the files it uses are unknown; fix or remove it first",
+                backup.id
+            ));
+        }
+        let referenced: HashSet<&String> = backups
+            .iter()
+            .filter_map(|b| b.manifest.as_ref()?.files.as_ref())
+            .flat_map(|files| files.values())
+            .collect();
+        let mut removed = vec![];
+        let Ok(prefixes) = fs::read_dir(&self.dir) else {
+            return Ok(removed);
+        };
+        for prefix in prefixes.filter_map(|e| e.ok()) {
+            for object in fs::read_dir(prefix.path())?.filter_map(|e| e.ok()) {
+                let name = object.file_name().to_string_lossy().to_string();
+                let recent = object
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .map(|time| !time.elapsed().is_ok_and(|age| age >= self.grace_period))
+                    .unwrap_or(true);
+                if !referenced.contains(&name) && !recent {
+                    if !dry_run {
+                        fs::remove_file(object.path())
+                            .with_context(|| format!("couldn't remove object {}", name))?;
+                    }
+                    removed.push(object.path());
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{save, TempDir};
+
+    #[test]
+    fn deduplication() {
+        let root = TempDir::new("store");
+        let store = Store::new(root.join("objects")).with_grace_period(Duration::ZERO);
+        let a = root.join("a");
+        let b = root.join("b");
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
+        fs::write(a.join("flags.txt"), b"same").unwrap();
+        fs::write(a.join("meta.txt"), b"one").unwrap();
+        fs::write(b.join("flags.txt"), b"same").unwrap();
+        fs::write(b.join("meta.txt"), b"two").unwrap();
+
+        let files_a = store.add_dir(&a).unwrap();
+        let files_b = store.add_dir(&b).unwrap();
+        assert_eq!(files_a["flags.txt"], files_b["flags.txt"]);
+        assert_ne!(files_a["meta.txt"], files_b["meta.txt"]);
+        assert_eq!(store.collect_garbage(&[], true).unwrap().len(), 3);
+
+        let out = root.join("out");
+        store.checkout(&files_b, &out).unwrap();
+        assert_eq!(fs::read(out.join("meta.txt")).unwrap(), b"two");
+    }
+
+    #[test]
+    fn garbage_collection_is_careful() {
+        let root = TempDir::new("store_gc");
+        let store = Store::new(root.join("objects"));
+        let a = root.join("a");
+        fs::create_dir_all(&a).unwrap();
+        fs::write(a.join("meta.txt"), b"one").unwrap();
+        store.add_dir(&a).unwrap();
+        // Just stored, so possibly for a backup that's still being made.
+        assert!(store.collect_garbage(&[], false).unwrap().is_empty());
+
+        let store = store.with_grace_period(Duration::ZERO);
+        let unreadable = Backup {
+            id: "unreadable".to_string(),
+            path: root.join("unreadable"),
+            manifest: None,
+            error: Some("invalid backup manifest".to_string()),
+            save: save(root.join("unreadable")),
+        };
+        assert!(store.collect_garbage(&[unreadable], false).is_err());
+        assert_eq!(store.collect_garbage(&[], false).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rejects_damaged_manifests() {
+        let root = TempDir::new("store_damaged");
+        let store = Store::new(root.join("objects"));
+        let out = root.join("out");
+        let valid = hash(b"data");
+
+        for (relative, hash) in [
+            ("meta.txt", "a"),
+            ("meta.txt", "é"),
+            ("meta.txt", &"g".repeat(64)),
+            ("../meta.txt", valid.as_str()),
+            ("/tmp/meta.txt", valid.as_str()),
+        ] {
+            let files = FileHashes::from([(relative.to_string(), hash.to_string())]);
+            assert!(store.checkout(&files, &out).is_err());
+            assert_eq!(store.size(&files), 0);
+        }
+        assert!(!root.join("meta.txt").exists());
+    }
+}
@@ -0,0 +1,72 @@
+//! Fixtures shared by the unit tests.
+
+use crate::decryption::encrypt;
+use crate::save::{Save, SavesData};
+use crate::sidecar::Sidecar;
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// Decrypted `meta.txt` of a level 1 save with a minute of play.
+pub const META: &[u8] = br#"{"level":1,"playTime":60.0,"gold":0,"gameMode":0,"atSceneGuid":""}"#;
+
+/// An empty directory under the system's temporary directory. It's
+/// removed when dropped, so also when a test fails.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates the directory, named after `name` and the test process.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("nine_saves_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Creates a save directory at `path` with [`META`] as its metadata.
+pub fn write_save(path: &Path) {
+    fs::create_dir_all(path).unwrap();
+    fs::write(path.join("meta.txt"), encrypt(META)).unwrap();
+}
+
+/// The save at `path`, named after it. Its info isn't read.
+pub fn save(path: PathBuf) -> Save {
+    Save {
+        name: path.file_name().unwrap().to_string_lossy().to_string(),
+        path,
+        nrp_backup: false,
+        exists: true,
+        info: None,
+        error: None,
+        sidecar: Sidecar::default(),
+    }
+}
+
+/// Directories for slots, external saves and backups under `root`, named
+/// `slots`, `saves` and `backups`. They're created, but nothing is loaded.
+pub fn saves_data(root: &Path) -> SavesData {
+    for dir in ["slots", "saves", "backups"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    SavesData {
+        game_slots_dir: root.join("slots"),
+        external_saves_dir: root.join("saves"),
+        backups_dir: root.join("backups"),
+        ..Default::default()
+    }
+}
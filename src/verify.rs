@@ -0,0 +1,139 @@
+use crate::archive;
+use crate::files;
+use crate::save::{Save, SavesData};
+use crate::store::{hash, FileHashes};
+
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+
+/// The state of an external save compared to its recorded checksums.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Integrity {
+    /// Every file matches.
+    Intact,
+    /// No checksums were recorded for the save.
+    Unrecorded,
+    /// These files changed, appeared or disappeared.
+    Damaged(Vec<String>),
+}
+
+/// Files in `expected` that are missing from `actual` or differ there.
+/// With `exact`, files only in `actual` count too.
+fn differences(expected: &FileHashes, actual: &FileHashes, exact: bool) -> Vec<String> {
+    let mut differing: Vec<String> = expected
+        .iter()
+        .filter(|(name, hash)| actual.get(*name) != Some(hash))
+        .map(|(name, _)| name.clone())
+        .collect();
+    if exact {
+        differing.extend(
+            actual
+                .keys()
+                .filter(|name| !expected.contains_key(*name))
+                .cloned(),
+        );
+    }
+    differing
+}
+
+impl Save {
+    /// The SHA-256 of each of the save's files.
+    pub fn checksums(&self) -> Result<FileHashes> {
+        match archive::is_archive(&self.path) {
+            true => Ok(archive::read_all(&self.path)?
+                .into_iter()
+                .map(|e| (e.name, hash(&e.data)))
+                .collect()),
+            false => files::walk(&self.path)?
+                .into_iter()
+                .map(|(relative, path)| {
+                    let data =
+                        fs::read(&path).with_context(|| format!("couldn't read {:?}", path))?;
+                    Ok((relative, hash(&data)))
+                })
+                .collect(),
+        }
+    }
+
+    /// Checks that every file of the save was copied to `copy` intact.
+    pub(crate) fn verify_copy(&self, copy: &Save) -> Result<()> {
+        let differing = differences(&self.checksums()?, &copy.checksums()?, false);
+        match differing.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!(
+                "copy of {} at {:?} doesn't match the original: {}",
+                self.name,
+                copy.path,
+                differing.join(", ")
+            )),
+        }
+    }
+
+    /// Compares the save's files to the checksums in its sidecar.
+    pub fn verify(&self) -> Result<Integrity> {
+        let Some(expected) = self.sidecar()?.checksums else {
+            return Ok(Integrity::Unrecorded);
+        };
+        let differing = differences(&expected, &self.checksums()?, true);
+        Ok(match differing.is_empty() {
+            true => Integrity::Intact,
+            false => Integrity::Damaged(differing),
+        })
+    }
+}
+
+impl SavesData {
+    /// Stores the checksums of `save` in its sidecar, if it's an external
+    /// save.
+    pub(crate) fn record_checksums(&self, save: &Save) -> Result<()> {
+        if !self.is_external(&save.path) {
+            return Ok(());
+        }
+        let mut sidecar = save.sidecar()?;
+        sidecar.checksums = Some(save.checksums()?);
+        save.write_sidecar(&sidecar)
+    }
+
+    /// Checks every external save against its recorded checksums. Saves
+    /// with none recorded have them recorded now, and are reported as
+    /// [`Integrity::Unrecorded`].
+    pub fn verify_library(&self) -> Result<Vec<(&Save, Integrity)>> {
+        self.saves
+            .iter()
+            .map(|save| {
+                let integrity = save
+                    .verify()
+                    .with_context(|| format!("couldn't verify {}", save.name))?;
+                if integrity == Integrity::Unrecorded {
+                    self.record_checksums(save)?;
+                }
+                Ok((save, integrity))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{save, saves_data, TempDir};
+
+    #[test]
+    fn detects_damage() {
+        let root = TempDir::new("verify");
+        let save = save(root.join("saves").join("a"));
+        fs::create_dir_all(&save.path).unwrap();
+        fs::write(save.path.join("meta.txt"), b"meta").unwrap();
+        let data = saves_data(&root);
+
+        assert_eq!(save.verify().unwrap(), Integrity::Unrecorded);
+        data.record_checksums(&save).unwrap();
+        assert_eq!(save.verify().unwrap(), Integrity::Intact);
+        fs::write(save.path.join("meta.txt"), b"mota").unwrap();
+        fs::write(save.path.join("extra.txt"), b"").unwrap();
+        assert_eq!(
+            save.verify().unwrap(),
+            Integrity::Damaged(vec!["meta.txt".to_string(), "extra.txt".to_string()])
+        );
+    }
+}
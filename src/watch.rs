@@ -0,0 +1,188 @@
+use crate::backup::{Backup, Operation};
+use crate::files;
+use crate::save::{game_slot, SavesData};
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a slot must go unchanged before it is snapshotted, so that a
+/// save the game is still in the middle of writing isn't captured.
+pub const QUIET_PERIOD: Duration = Duration::from_secs(5);
+
+/// The relative path, size and modification time of each file in a slot,
+/// including those in subdirectories.
+type Fingerprint = Vec<(String, u64, Option<SystemTime>)>;
+
+fn fingerprint(dir: &Path) -> Fingerprint {
+    let Ok(entries) = files::walk(dir) else {
+        return vec![];
+    };
+    let mut files: Fingerprint = entries
+        .into_iter()
+        .filter_map(|(relative, path)| {
+            let metadata = fs::metadata(path).ok()?;
+            Some((relative, metadata.len(), metadata.modified().ok()))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+#[derive(Debug)]
+struct Pending {
+    fingerprint: Fingerprint,
+    since: Instant,
+}
+
+/// Watches the game's slots and snapshots each one after the game writes
+/// to it.
+///
+/// Call [`SlotWatcher::poll`] every second or so. A slot is snapshotted
+/// once its files have stopped changing for the quiet period and its
+/// metadata can be read. Old snapshots are removed by
+/// [`SavesData::prune_backups`] according to the retention policy.
+#[derive(Debug)]
+pub struct SlotWatcher {
+    quiet_period: Duration,
+    /// Each slot as of its last snapshot, or as it was when watching began.
+    known: HashMap<PathBuf, Fingerprint>,
+    pending: HashMap<PathBuf, Pending>,
+}
+
+fn slot_dirs(data: &SavesData) -> Result<Vec<PathBuf>> {
+    Ok(fs::read_dir(&data.game_slots_dir)
+        .context("couldn't read game's slot directory")?
+        .filter_map(|x| x.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir() && game_slot(p).is_some())
+        .collect())
+}
+
+impl SlotWatcher {
+    /// Starts watching the slots in `data.game_slots_dir`. Only changes
+    /// made from now on are snapshotted.
+    pub fn new(data: &SavesData) -> Result<Self> {
+        let known = slot_dirs(data)?
+            .into_iter()
+            .map(|dir| {
+                let fingerprint = fingerprint(&dir);
+                (dir, fingerprint)
+            })
+            .collect();
+        Ok(Self {
+            quiet_period: QUIET_PERIOD,
+            known,
+            pending: HashMap::new(),
+        })
+    }
+
+    pub fn with_quiet_period(self, quiet_period: Duration) -> Self {
+        Self {
+            quiet_period,
+            ..self
+        }
+    }
+
+    /// Checks the slots for changes, and snapshots those that have settled.
+    /// Returns the new snapshots.
+    pub fn poll(&mut self, data: &SavesData) -> Result<Vec<Backup>> {
+        self.poll_at(data, Instant::now())
+    }
+
+    fn poll_at(&mut self, data: &SavesData, now: Instant) -> Result<Vec<Backup>> {
+        let dirs = slot_dirs(data)?;
+        // Forget deleted slots, so they're snapshotted when they come back.
+        self.known.retain(|dir, _| dirs.contains(dir));
+        self.pending.retain(|dir, _| dirs.contains(dir));
+
+        let mut snapshots = vec![];
+        for dir in dirs {
+            let current = fingerprint(&dir);
+            if self.known.get(&dir) == Some(&current) {
+                self.pending.remove(&dir);
+                continue;
+            }
+            match self.pending.get(&dir) {
+                Some(p) if p.fingerprint == current => {
+                    if now.duration_since(p.since) < self.quiet_period {
+                        continue;
+                    }
+                }
+                _ => {
+                    self.pending.insert(
+                        dir,
+                        Pending {
+                            fingerprint: current,
+                            since: now,
+                        },
+                    );
+                    continue;
+                }
+            }
+            let slot = game_slot(&dir).expect("slot_dirs only returns slots");
+            // Metadata that doesn't decrypt means the write isn't finished,
+            // so wait for the next change.
+            let Ok(slot) = slot.with_decrypted_info() else {
+                continue;
+            };
+            snapshots.push(
+                data.backup(&slot, Operation::Snapshot)
+                    .with_context(|| format!("failed to snapshot {}", slot.name))?,
+            );
+            self.pending.remove(&dir);
+            self.known.insert(dir, current);
+        }
+        Ok(snapshots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{saves_data, write_save, TempDir};
+
+    #[test]
+    fn debounce() {
+        let root = TempDir::new("watch");
+        let slot = root.join("slots").join("saveslot0");
+        write_save(&slot);
+        let data = saves_data(&root);
+
+        let mut watcher = SlotWatcher::new(&data).unwrap();
+        let start = Instant::now();
+        assert!(watcher.poll_at(&data, start).unwrap().is_empty());
+
+        fs::write(slot.join("flags.txt"), b"partial").unwrap();
+        assert!(watcher.poll_at(&data, start).unwrap().is_empty());
+        fs::write(slot.join("flags.txt"), b"complete").unwrap();
+        let later = start + QUIET_PERIOD;
+        assert!(watcher.poll_at(&data, later).unwrap().is_empty());
+        let snapshots = watcher.poll_at(&data, later + QUIET_PERIOD).unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].save.name, "Slot 1");
+        assert!(watcher
+            .poll_at(&data, later + QUIET_PERIOD * 2)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn nested_changes() {
+        let root = TempDir::new("watch_nested");
+        let slot = root.join("slots").join("saveslot0");
+        write_save(&slot);
+        fs::create_dir_all(slot.join("scenes")).unwrap();
+        fs::write(slot.join("scenes").join("a.txt"), b"before").unwrap();
+        let data = saves_data(&root);
+
+        let mut watcher = SlotWatcher::new(&data).unwrap();
+        let start = Instant::now();
+        fs::write(slot.join("scenes").join("a.txt"), b"after!").unwrap();
+        assert!(watcher.poll_at(&data, start).unwrap().is_empty());
+        let snapshots = watcher.poll_at(&data, start + QUIET_PERIOD).unwrap();
+        assert_eq!(snapshots.len(), 1);
+    }
+}